@@ -1,8 +1,39 @@
 use serde::{Serialize, Deserialize};
 
+// shared with the solver, which the web frontend can't depend on
+#[path = "../../../algorithm/src/bytes.rs"]
+mod bytes;
+
+pub use bytes::Bytes;
+
 #[derive(Serialize, Deserialize)]
 pub enum AlgorithmProgress {
     NoAlgorithmRunning,
     InProgress { progress: usize, out_of: usize },
-    Done(Option<Vec<i64>>),
+    Done { output: Option<Vec<i64>>, found_early_at: Option<usize> },
+    OutOfTime(Closest),
+    Failed(String),
+}
+
+/// The answer to a request to resume a solve from a checkpoint file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ResumeResult {
+    /// The solve is running again; these are the target and numbers it was saved with.
+    Resumed { target: i64, number_set: Vec<i64> },
+    Failed(String),
+}
+
+/// The closest subset found when the time limit ran out before an exact answer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Closest {
+    pub subset: Vec<i64>,
+    pub sum: i64,
+    pub rows_completed: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MemoryEstimate {
+    pub strategy: String,
+    pub bytes: u64,
+    pub fits_budget: bool,
 }
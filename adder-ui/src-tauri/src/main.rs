@@ -3,26 +3,82 @@
     windows_subsystem = "windows"
 )]
 
-use std::{sync::{Arc, atomic::{AtomicUsize, Ordering}, mpsc, Mutex}, thread};
+use std::{path::PathBuf, sync::{Arc, atomic::{AtomicUsize, Ordering}, mpsc, Mutex}, thread, time::{Duration, Instant}};
 
-use adder_ui_model::AlgorithmProgress;
+use adder_algorithm::{CheckpointHeader, CheckpointOptions, IncrementalSolver, Observer, Options, Outcome, SolveError, Strategy};
+use adder_ui_model::{AlgorithmProgress, Closest, MemoryEstimate, ResumeResult};
+
+#[derive(Default)]
+struct Progress {
+    row:            AtomicUsize,
+    found_early_at: Mutex<Option<usize>>,
+}
+
+impl Observer for Progress {
+    fn row_started(&self, row: usize, _total: usize) {
+        self.row.store(row, Ordering::Relaxed);
+    }
+
+    fn found_early(&self, row: usize, _total: usize) {
+        *self.found_early_at.lock().unwrap() = Some(row);
+    }
+}
 
 struct Global {
-    progress: Arc<AtomicUsize>,
+    progress: Arc<Progress>,
     out_of:   usize,
-    receiver: mpsc::Receiver<Option<Vec<i64>>>,
+    receiver: mpsc::Receiver<Result<Outcome, SolveError>>,
 }
 
 static GLOBAL: Mutex<Option<Global>> = Mutex::new(None);
 
+/// The table from the last plain run, so that the next one only fills the rows for
+/// numbers that were added or came after a changed one.
+struct Incremental {
+    memory_budget: Option<u64>,
+    threads:       Option<usize>,
+    solver:        IncrementalSolver,
+}
+
+static INCREMENTAL: Mutex<Option<Incremental>> = Mutex::new(None);
+
+/// Rows filled between checkpoints.
+const CHECKPOINT_EVERY: usize = 100;
+
 #[tauri::command]
-fn run_algorithm(target: i64, number_set: Vec<i64>) {
-    println!("Hello from tauri!");
-    println!("target: {target}, set: {number_set:?}");
+fn estimate_memory(number_set: Vec<i64>, memory_budget: Option<u64>) -> Vec<MemoryEstimate> {
+    Strategy::ALL.into_iter()
+        .map(|strategy| {
+            let bytes = adder_algorithm::estimate_memory(&number_set, strategy);
 
+            MemoryEstimate {
+                strategy: strategy.to_string(),
+                bytes,
+                fits_budget: memory_budget.map_or(true, |budget| bytes <= budget),
+            }
+        })
+        .collect()
+}
+
+fn options(memory_budget: Option<u64>, threads: Option<usize>, early_exit: bool, time_limit_secs: Option<f64>, checkpoint_path: Option<String>) -> Options {
+    Options {
+        memory_budget,
+        threads,
+        early_exit,
+        deadline: time_limit_secs
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .map(|limit| Instant::now() + limit),
+        checkpoint: checkpoint_path
+            .filter(|path| !path.trim().is_empty())
+            .map(|path| CheckpointOptions { path: PathBuf::from(path), every: CHECKPOINT_EVERY }),
+        ..Options::default()
+    }
+}
+
+/// Runs `solve` on a new thread, reporting to the progress `check_algorithm` polls.
+fn start(out_of: usize, solve: impl FnOnce(&Progress) -> Result<Outcome, SolveError> + Send + 'static) {
     let (sender, receiver) = mpsc::channel();
-    let progress = Arc::new(AtomicUsize::new(0));
-    let out_of = number_set.len();
+    let progress = Arc::new(Progress::default());
 
     *GLOBAL.lock().unwrap() = Some(
         Global {
@@ -33,11 +89,90 @@ fn run_algorithm(target: i64, number_set: Vec<i64>) {
     );
 
     thread::spawn(move || {
-        let answer = adder_algorithm::run_algorithm(target, number_set, Some(progress.as_ref()));
+        let answer = solve(progress.as_ref());
         let _ = sender.send(answer);
     });
 }
 
+#[tauri::command]
+fn run_algorithm(target: i64, number_set: Vec<i64>, memory_budget: Option<u64>, threads: Option<usize>, early_exit: bool, time_limit_secs: Option<f64>, checkpoint_path: Option<String>) {
+    println!("Hello from tauri!");
+    println!("target: {target}, set: {number_set:?}, memory budget: {memory_budget:?}, threads: {threads:?}, early exit: {early_exit}, time limit: {time_limit_secs:?}, checkpoint: {checkpoint_path:?}");
+
+    let options = options(memory_budget, threads, early_exit, time_limit_secs, checkpoint_path);
+
+    // the kept table is always filled whole, so runs that may stop filling partway (early
+    // exit or a time limit) or save rows as they go (a checkpoint) start from scratch
+    let plain = !options.early_exit && options.deadline.is_none() && options.checkpoint.is_none();
+
+    start(number_set.len(), move |progress| {
+        if plain {
+            run_incremental(target, number_set, &options, progress)
+        } else {
+            adder_algorithm::run_algorithm(target, number_set, &options, Some(progress))
+        }
+    });
+}
+
+/// Solves with the table kept from the last plain run, if it was made with the same
+/// options. Falls back to a solve from scratch, which may pick the low-memory strategy,
+/// if the whole table doesn't fit the memory budget.
+///
+/// The table is taken out of `INCREMENTAL` while it is filled and walked, so the lock
+/// is only held to take it and put it back.
+fn run_incremental(target: i64, number_set: Vec<i64>, options: &Options, progress: &Progress) -> Result<Outcome, SolveError> {
+    let kept = INCREMENTAL.lock().unwrap().take()
+        .filter(|kept| kept.memory_budget == options.memory_budget && kept.threads == options.threads);
+    let mut incremental = match kept {
+        Some(kept) => kept,
+        None => Incremental {
+            memory_budget: options.memory_budget,
+            threads:       options.threads,
+            solver:        IncrementalSolver::new(target, options)?,
+        },
+    };
+
+    let solver = &mut incremental.solver;
+    solver.set_target(target);
+
+    match solver.set_entries(number_set.clone(), Some(progress)) {
+        Ok(kept) => log::info!("Reused {kept} rows from the last run"),
+        Err(SolveError::MemoryBudgetExceeded { .. }) => {
+            // free the old table before the solve from scratch allocates its own
+            drop(incremental);
+            return adder_algorithm::run_algorithm(target, number_set, options, Some(progress));
+        }
+        Err(err) => {
+            // the table is unchanged on error, so it can still be reused
+            *INCREMENTAL.lock().unwrap() = Some(incremental);
+            return Err(err);
+        }
+    }
+
+    let solution = solver.solve();
+    *INCREMENTAL.lock().unwrap() = Some(incremental);
+
+    Ok(Outcome::Exact(solution))
+}
+
+#[tauri::command]
+fn resume_algorithm(checkpoint_path: String, memory_budget: Option<u64>, threads: Option<usize>, early_exit: bool, time_limit_secs: Option<f64>) -> ResumeResult {
+    log::info!("Resuming from {checkpoint_path}");
+
+    let header = match CheckpointHeader::read(checkpoint_path.as_ref()) {
+        Ok(header) => header,
+        Err(err) => return ResumeResult::Failed(err.to_string()),
+    };
+
+    let options = options(memory_budget, threads, early_exit, time_limit_secs, Some(checkpoint_path));
+
+    start(header.entries.len(), move |progress| {
+        adder_algorithm::resume_algorithm(&options, Some(progress))
+    });
+
+    ResumeResult::Resumed { target: header.target, number_set: header.entries }
+}
+
 #[tauri::command]
 fn check_algorithm() -> AlgorithmProgress {
     let mut lock = GLOBAL.lock().unwrap();
@@ -48,18 +183,29 @@ fn check_algorithm() -> AlgorithmProgress {
 
     if let Ok(output) = global.receiver.try_recv() {
         *lock = None;
-        return AlgorithmProgress::Done(output);
+        return match output {
+            Ok(Outcome::Exact(output)) => AlgorithmProgress::Done {
+                output: output.map(|solution| solution.amounts),
+                found_early_at: *global.progress.found_early_at.lock().unwrap(),
+            },
+            Ok(Outcome::BestEffort(best)) => AlgorithmProgress::OutOfTime(Closest {
+                subset:         best.closest.amounts,
+                sum:            best.closest.sum,
+                rows_completed: best.rows_completed,
+            }),
+            Err(err)   => AlgorithmProgress::Failed(err.to_string()),
+        };
     }
 
     return AlgorithmProgress::InProgress {
-        progress: global.progress.load(Ordering::SeqCst),
+        progress: global.progress.row.load(Ordering::Relaxed),
         out_of:   global.out_of,
     };
 }
 
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![run_algorithm, check_algorithm])
+        .invoke_handler(tauri::generate_handler![estimate_memory, run_algorithm, resume_algorithm, check_algorithm])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
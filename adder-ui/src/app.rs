@@ -1,6 +1,6 @@
 use std::{rc::Rc, time::Duration};
 
-use adder_ui_model::AlgorithmProgress;
+use adder_ui_model::{AlgorithmProgress, Bytes, Closest, MemoryEstimate, ResumeResult};
 use futures_timer::Delay;
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::to_value;
@@ -75,12 +75,53 @@ fn reason_for_invalid_number(offender: &str) -> Html {
 struct RunAlgorithmArgs {
     target: i64,
     numberSet: Vec<i64>,
+    memoryBudget: Option<u64>,
+    threads: Option<usize>,
+    earlyExit: bool,
+    timeLimitSecs: Option<f64>,
+    checkpointPath: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize)]
+struct ResumeAlgorithmArgs {
+    checkpointPath: String,
+    memoryBudget: Option<u64>,
+    threads: Option<usize>,
+    earlyExit: bool,
+    timeLimitSecs: Option<f64>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize)]
+struct EstimateMemoryArgs {
+    numberSet: Vec<i64>,
+    memoryBudget: Option<u64>,
+}
+
+pub struct CalculateRequest {
+    target: i64,
+    number_set: Vec<i64>,
+    memory_budget: Option<u64>,
+    threads: Option<usize>,
+    early_exit: bool,
+    time_limit_secs: Option<f64>,
+    checkpoint_path: Option<String>,
+}
+
+pub struct ResumeRequest {
+    checkpoint_path: String,
+    memory_budget: Option<u64>,
+    threads: Option<usize>,
+    early_exit: bool,
+    time_limit_secs: Option<f64>,
 }
 
 enum AppState {
     GatheringInput,
     Calculating(i64, Vec<i64>),
     Result(CalculationComplete),
+    Failed(String),
 }
 
 #[function_component(App)]
@@ -90,7 +131,7 @@ pub fn app() -> Html {
     let on_calculate = {
         let app_state = app_state.clone();
 
-        Callback::from(move |(target, number_set): (i64, Vec<i64>)| {
+        Callback::from(move |CalculateRequest { target, number_set, memory_budget, threads, early_exit, time_limit_secs, checkpoint_path }| {
             let app_state = app_state.clone();
 
             log("At callback in app");
@@ -98,7 +139,7 @@ pub fn app() -> Html {
                 log("About to invoke run_algorithm");
                 invoke(
                     "run_algorithm",
-                    to_value(&RunAlgorithmArgs { target, numberSet: number_set.clone() }).unwrap(),
+                    to_value(&RunAlgorithmArgs { target, numberSet: number_set.clone(), memoryBudget: memory_budget, threads, earlyExit: early_exit, timeLimitSecs: time_limit_secs, checkpointPath: checkpoint_path }).unwrap(),
                 )
                 .await;
                 log("run_algorithm invoked");
@@ -108,6 +149,29 @@ pub fn app() -> Html {
         })
     };
 
+    let on_resume = {
+        let app_state = app_state.clone();
+
+        Callback::from(move |ResumeRequest { checkpoint_path, memory_budget, threads, early_exit, time_limit_secs }| {
+            let app_state = app_state.clone();
+
+            spawn_local(async move {
+                log("About to invoke resume_algorithm");
+                let js_value = invoke(
+                    "resume_algorithm",
+                    to_value(&ResumeAlgorithmArgs { checkpointPath: checkpoint_path, memoryBudget: memory_budget, threads, earlyExit: early_exit, timeLimitSecs: time_limit_secs }).unwrap(),
+                )
+                .await;
+                log("resume_algorithm invoked");
+
+                match serde_wasm_bindgen::from_value(js_value).unwrap() {
+                    ResumeResult::Resumed { target, number_set } => app_state.set(AppState::Calculating(target, number_set)),
+                    ResumeResult::Failed(message) => app_state.set(AppState::Failed(message)),
+                }
+            });
+        })
+    };
+
     let on_complete = {
         let app_state = app_state.clone();
 
@@ -117,6 +181,15 @@ pub fn app() -> Html {
         })
     };
 
+    let on_failed = {
+        let app_state = app_state.clone();
+
+        Callback::from(move |message| {
+            log("Failed");
+            app_state.set(AppState::Failed(message));
+        })
+    };
+
     let on_return = {
         let app_state = app_state.clone();
 
@@ -132,18 +205,22 @@ pub fn app() -> Html {
                 {
                     match &*app_state {
                         AppState::GatheringInput => html! {
-                            <GatheringInput on_calculate={on_calculate} />
+                            <GatheringInput on_calculate={on_calculate} on_resume={on_resume} />
                         },
                         AppState::Calculating(target, number_set) => html! {
                             <Calculating
                                 target={*target}
                                 number_set={number_set.clone()}
                                 on_complete={on_complete}
+                                on_failed={on_failed}
                             />
                         },
                         AppState::Result(output) => html! {
                             <AlgorithmResult output={output.clone()} on_return={on_return} />
                         },
+                        AppState::Failed(message) => html! {
+                            <AlgorithmFailed message={message.clone()} on_return={on_return} />
+                        },
                     }
                 }
             </Container>
@@ -183,15 +260,52 @@ pub fn algorithm_result(props: &AlgorithmResultProps) -> Html {
                                     <label classes="label">{ "The following numbers add up to exactly " } { props.output.target }</label>
                                     <TextArea name="output" value={output} update={Callback::from(|_| {})} readonly={true} rows={correct_set.len() as u32} />
                                 </Block>
+                                {
+                                    match props.output.found_early_at {
+                                        Some(row) => html! {
+                                            <Block>
+                                                { "Found early: only the first " } { row + 1 } { " of " } { props.output.number_set.len() } { " numbers had to be checked." }
+                                            </Block>
+                                        },
+                                        None => html! {},
+                                    }
+                                }
                             </>
                         }
                     }
-                    None => html! {
-                        <>
-                            <Block>
-                                <h3>{ "Sorry... no exact set exists" }</h3>
-                            </Block>
-                        </>
+                    None => match props.output.closest.as_ref() {
+                        Some(closest) => {
+                            let output = closest.subset.iter()
+                                .map(|num| num.to_string())
+                                .collect::<Vec<_>>()
+                                .join("\n");
+
+                            html! {
+                                <>
+                                    <Block>
+                                        <h3>{ "Ran out of time" }</h3>
+                                    </Block>
+                                    <Block>
+                                        { "Only the first " } { closest.rows_completed } { " of " } { props.output.number_set.len() }
+                                        { " numbers were fully checked, so an exact set may still exist." }
+                                    </Block>
+                                    <Block>
+                                        <label classes="label">
+                                            { "The closest set found adds up to " } { closest.sum }
+                                            { " (" } { closest.sum.abs_diff(props.output.target) } { " away from " } { props.output.target } { ")" }
+                                        </label>
+                                        <TextArea name="output" value={output} update={Callback::from(|_| {})} readonly={true} rows={closest.subset.len() as u32} />
+                                    </Block>
+                                </>
+                            }
+                        }
+                        None => html! {
+                            <>
+                                <Block>
+                                    <h3>{ "Sorry... no exact set exists" }</h3>
+                                </Block>
+                            </>
+                        },
                     },
                 }
             }
@@ -202,11 +316,38 @@ pub fn algorithm_result(props: &AlgorithmResultProps) -> Html {
     }
 }
 
+#[derive(Properties, PartialEq)]
+pub struct AlgorithmFailedProps {
+    message: String,
+    on_return: Callback<()>,
+}
+
+#[function_component(AlgorithmFailed)]
+pub fn algorithm_failed(props: &AlgorithmFailedProps) -> Html {
+    let on_return = props.on_return.clone();
+    let on_click = Callback::from(move |_| {
+        on_return.emit(());
+    });
+
+    html! {
+        <>
+            <Notification classes="is-danger">
+                <p>{ "The algorithm could not run: " }</p>
+                <p><strong>{ &props.message }</strong></p>
+            </Notification>
+            <Block>
+                <Button classes="is-link" onclick={on_click}>{ "Return to menu" }</Button>
+            </Block>
+        </>
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct CalculatingProps {
     target: i64,
     number_set: Vec<i64>,
     on_complete: Callback<CalculationComplete>,
+    on_failed: Callback<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -214,6 +355,8 @@ pub struct CalculationComplete {
     target: i64,
     number_set: Vec<i64>,
     correct_set: Option<Vec<i64>>,
+    found_early_at: Option<usize>,
+    closest: Option<Closest>,
 }
 
 #[function_component(Calculating)]
@@ -225,12 +368,14 @@ pub fn calculating(props: &CalculatingProps) -> Html {
 
     {
         let on_complete = props.on_complete.clone();
+        let on_failed = props.on_failed.clone();
         let progress_out_of = progress_out_of.clone();
         let target = target.clone();
         let number_set = number_set.clone();
 
         spawn_local(self_referential_future(move |future_self| {
             let on_complete = on_complete.clone();
+            let on_failed = on_failed.clone();
             let progress_out_of = progress_out_of.clone();
             let target = target.clone();
             let number_set = number_set.clone();
@@ -255,15 +400,33 @@ pub fn calculating(props: &CalculatingProps) -> Html {
                         log(&format!("In progress: {progress}/{out_of}"));
                         progress_out_of.set((progress, out_of));
                     }
-                    AlgorithmProgress::Done(output) => {
+                    AlgorithmProgress::Done { output, found_early_at } => {
                         log(&format!("Done! Output: {output:?}"));
                         on_complete.emit(CalculationComplete {
                             target: *target,
                             number_set: (*number_set).clone(),
                             correct_set: output,
+                            found_early_at,
+                            closest: None,
                         });
                         return;
                     }
+                    AlgorithmProgress::OutOfTime(closest) => {
+                        log(&format!("Out of time! Closest: {closest:?}"));
+                        on_complete.emit(CalculationComplete {
+                            target: *target,
+                            number_set: (*number_set).clone(),
+                            correct_set: None,
+                            found_early_at: None,
+                            closest: Some(closest),
+                        });
+                        return;
+                    }
+                    AlgorithmProgress::Failed(message) => {
+                        log(&format!("Failed: {message}"));
+                        on_failed.emit(message);
+                        return;
+                    }
                 }
 
                 Delay::new(Duration::from_millis(100)).await;
@@ -289,7 +452,8 @@ pub fn calculating(props: &CalculatingProps) -> Html {
 
 #[derive(Properties, PartialEq)]
 pub struct GatheringInputProps {
-    pub on_calculate: Callback<(i64, Vec<i64>)>,
+    pub on_calculate: Callback<CalculateRequest>,
+    pub on_resume: Callback<ResumeRequest>,
 }
 
 #[function_component(GatheringInput)]
@@ -318,8 +482,93 @@ pub fn gathering_input(props: &GatheringInputProps) -> Html {
         })
     };
 
-    let target     = Rc::new(target_input.parse::<i64>());
-    let number_set = Rc::new(number_set(numbers_input.as_str()));
+    let memory_budget_input = use_state(|| String::from(""));
+    let memory_budget_input_callback = {
+        let memory_budget_input = memory_budget_input.clone();
+        Callback::from(move |new_value: String| {
+            memory_budget_input.set(new_value);
+        })
+    };
+
+    let threads_input = use_state(|| String::from(""));
+    let threads_input_callback = {
+        let threads_input = threads_input.clone();
+        Callback::from(move |new_value: String| {
+            threads_input.set(
+                new_value.chars()
+                    .filter(|&ch| ch.is_ascii_digit())
+                    .collect()
+            );
+        })
+    };
+
+    let time_limit_input = use_state(|| String::from(""));
+    let time_limit_input_callback = {
+        let time_limit_input = time_limit_input.clone();
+        Callback::from(move |new_value: String| {
+            time_limit_input.set(
+                new_value.chars()
+                    .filter(|&ch| ch.is_ascii_digit() || ch == '.')
+                    .collect()
+            );
+        })
+    };
+
+    let checkpoint_input = use_state(|| String::from(""));
+    let checkpoint_input_callback = {
+        let checkpoint_input = checkpoint_input.clone();
+        Callback::from(move |new_value: String| {
+            checkpoint_input.set(new_value);
+        })
+    };
+
+    let early_exit = use_state(|| true);
+    let early_exit_callback = {
+        let early_exit = early_exit.clone();
+        Callback::from(move |checked: bool| {
+            early_exit.set(checked);
+        })
+    };
+
+    let target        = Rc::new(target_input.parse::<i64>());
+    let number_set    = Rc::new(number_set(numbers_input.as_str()));
+    let memory_budget = memory_budget_input.parse::<Bytes>().ok().map(|Bytes(budget)| budget);
+    let memory_budget_valid = memory_budget.is_some() || memory_budget_input.trim().is_empty();
+    let threads       = threads_input.parse::<usize>().ok().filter(|&threads| threads > 0);
+    let time_limit_secs = time_limit_input.parse::<f64>().ok().filter(|&secs| secs > 0.0);
+    let checkpoint_path = Some(checkpoint_input.trim().to_string()).filter(|path| !path.is_empty());
+
+    let memory_estimates = use_state(|| None::<Vec<MemoryEstimate>>);
+    {
+        let memory_estimates = memory_estimates.clone();
+        let estimate_for = match &*number_set {
+            Ok(number_set) if !number_set.is_empty() => Some((number_set.clone(), memory_budget)),
+            _ => None,
+        };
+
+        use_effect_with_deps(
+            move |estimate_for| {
+                match estimate_for.clone() {
+                    Some((number_set, memory_budget)) => spawn_local(async move {
+                        let js_value = invoke(
+                            "estimate_memory",
+                            to_value(&EstimateMemoryArgs { numberSet: number_set, memoryBudget: memory_budget }).unwrap(),
+                        )
+                        .await;
+
+                        memory_estimates.set(serde_wasm_bindgen::from_value(js_value).ok());
+                    }),
+                    None => memory_estimates.set(None),
+                }
+
+                || {}
+            },
+            estimate_for,
+        );
+    }
+
+    let fits_budget = memory_estimates.as_ref()
+        .map_or(true, |estimates| estimates.iter().any(|estimate| estimate.fits_budget));
 
     let button_clicked = use_state(|| false);
 
@@ -328,21 +577,38 @@ pub fn gathering_input(props: &GatheringInputProps) -> Html {
         let number_set = number_set.clone();
         let on_calculate = props.on_calculate.clone();
         let button_clicked = button_clicked.clone();
+        let early_exit = *early_exit;
+        let checkpoint_path = checkpoint_path.clone();
 
         Callback::from(move |_| {
             button_clicked.set(true);
 
             let target     = *target.as_ref().as_ref().unwrap();
             let number_set = number_set.as_ref().as_ref().unwrap().clone();
+            let checkpoint_path = checkpoint_path.clone();
 
             log("Button clicked, emitting event...");
 
-            on_calculate.emit((target, number_set));
+            on_calculate.emit(CalculateRequest { target, number_set, memory_budget, threads, early_exit, time_limit_secs, checkpoint_path });
             
             log("...Event emitted.");
         })
     };
 
+    let resume_onclick = {
+        let on_resume = props.on_resume.clone();
+        let button_clicked = button_clicked.clone();
+        let early_exit = *early_exit;
+        let checkpoint_path = checkpoint_path.clone();
+
+        Callback::from(move |_| {
+            button_clicked.set(true);
+
+            let checkpoint_path = checkpoint_path.clone().unwrap();
+            on_resume.emit(ResumeRequest { checkpoint_path, memory_budget, threads, early_exit, time_limit_secs });
+        })
+    };
+
     html! {
         <>
             <div class="field">
@@ -366,6 +632,87 @@ pub fn gathering_input(props: &GatheringInputProps) -> Html {
                 />
             </div>
 
+            <div class="field">
+                <label class="label">{ "Memory budget (optional, e.g. 512M or 8G)" }</label>
+                <Input
+                    name="memory_budget"
+                    value={memory_budget_input.to_string()}
+                    update={memory_budget_input_callback}
+                    placeholder={"Unlimited"}
+                />
+                {
+                    if memory_budget_valid {
+                        html! {}
+                    } else {
+                        html! { <p class="help is-danger">{ "Invalid memory budget: " } { memory_budget_input.as_str() }</p> }
+                    }
+                }
+            </div>
+
+            <div class="field">
+                <label class="label">{ "Threads (optional, leave empty to use every core)" }</label>
+                <Input
+                    name="threads"
+                    value={threads_input.to_string()}
+                    update={threads_input_callback}
+                    placeholder={"All cores"}
+                />
+            </div>
+
+            <div class="field">
+                <label class="label">{ "Time limit in seconds (optional)" }</label>
+                <Input
+                    name="time_limit"
+                    value={time_limit_input.to_string()}
+                    update={time_limit_input_callback}
+                    placeholder={"No limit"}
+                />
+            </div>
+
+            <div class="field">
+                <label class="label">{ "Checkpoint file (optional, saves progress so a run can be resumed)" }</label>
+                <Input
+                    name="checkpoint"
+                    value={checkpoint_input.to_string()}
+                    update={checkpoint_input_callback}
+                    placeholder={"No checkpoint"}
+                />
+            </div>
+
+            <div class="field">
+                <Button
+                    disabled={*button_clicked || checkpoint_path.is_none() || !memory_budget_valid}
+                    onclick={resume_onclick}
+                >
+                    { "Resume from checkpoint" }
+                </Button>
+            </div>
+
+            <div class="field">
+                <Checkbox name="early_exit" checked={*early_exit} update={early_exit_callback}>
+                    { " Stop as soon as a correct set is found" }
+                </Checkbox>
+            </div>
+
+            {
+                match &*memory_estimates {
+                    Some(estimates) => html! {
+                        <Block>
+                            <label class="label">{ "Estimated memory" }</label>
+                            {
+                                for estimates.iter().map(|estimate| html! {
+                                    <p>
+                                        { &estimate.strategy } { ": " } { Bytes(estimate.bytes).to_string() }
+                                        { if estimate.fits_budget { "" } else { " (over budget)" } }
+                                    </p>
+                                })
+                            }
+                        </Block>
+                    },
+                    None => html! {},
+                }
+            }
+
             {
                 if target.is_ok() || (target.is_err() && target_input.as_str() == "") {
                     match &*number_set {
@@ -374,7 +721,7 @@ pub fn gathering_input(props: &GatheringInputProps) -> Html {
                                 <div class="field">
                                     <Button
                                         classes="is-link"
-                                        disabled={*button_clicked || target_input.is_empty() || number_set.is_empty()}
+                                        disabled={*button_clicked || target_input.is_empty() || number_set.is_empty() || !memory_budget_valid || !fits_budget}
                                         onclick={button_onclick}
                                     >
                                         { "Calculate" }
@@ -0,0 +1,113 @@
+//! Compares the kernels used to fill DP rows with each other, with the per-bit atomic
+//! loop they replaced, and with the same loop over whole atomic blocks.
+
+use std::sync::atomic::Ordering;
+
+use adder_algorithm::{kernel, Kernel};
+use atomic_bitvec::{AtomicBitVec, BitVec};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+/// A row with roughly every third bit set, deterministic so runs are comparable.
+fn sample_row(bits: usize) -> Vec<u64> {
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    (0..kernel::words_for(bits))
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state & (state >> 1)
+        })
+        .collect()
+}
+
+fn to_atomic(row: &[u64]) -> AtomicBitVec {
+    BitVec::from_blocks(row.to_vec(), row.len() * 64).into_atomic()
+}
+
+/// The row transition as it was written before rows became plain words.
+fn per_bit_atomic(previous: &AtomicBitVec, row: &AtomicBitVec, sum_size: usize, shift: isize) {
+    (0..sum_size).into_par_iter()
+        .for_each(|j| {
+            if previous.get(j, Ordering::SeqCst) {
+                row.set(j, true, Ordering::SeqCst);
+            }
+        });
+
+    (0..sum_size).into_par_iter()
+        .for_each(|j| {
+            let index = (j as isize) - shift;
+            if index < 0 || index as usize >= sum_size {
+                return;
+            }
+
+            if previous.get(index as usize, Ordering::SeqCst) {
+                row.set(j, true, Ordering::SeqCst);
+            }
+        });
+}
+
+/// The same transition a block at a time, copying `previous` over in parallel and then
+/// shifting it in with the bitvec's own parallel shifted OR.
+fn per_block_atomic(previous: &AtomicBitVec, row: &AtomicBitVec, shift: isize) {
+    row.par_blocks()
+        .zip(previous.par_blocks())
+        .for_each(|(block, previous)| {
+            block.fetch_or(previous.load(Ordering::Acquire), Ordering::AcqRel);
+        });
+
+    row.or_shifted_from(previous, shift, Ordering::AcqRel);
+}
+
+fn row_transition(c: &mut Criterion) {
+    let mut group = c.benchmark_group("row_transition");
+
+    for sum_size in [1 << 16, 1 << 20, 1 << 24] {
+        let shift = sum_size as isize / 3 + 5;
+        let previous = sample_row(sum_size);
+        group.throughput(Throughput::Elements(sum_size as u64));
+
+        for kernel in Kernel::ALL.into_iter().filter(|kernel| kernel.is_available()) {
+            group.bench_with_input(BenchmarkId::new(kernel.to_string(), sum_size), &sum_size, |b, _| {
+                let mut row = vec![0; previous.len()];
+                b.iter(|| kernel.shift_or(&previous, &mut row, shift));
+            });
+        }
+
+        if sum_size <= 1 << 20 {
+            let previous = to_atomic(&previous);
+            group.bench_with_input(BenchmarkId::new("per_bit_atomic", sum_size), &sum_size, |b, &sum_size| {
+                let row = to_atomic(&vec![0; kernel::words_for(sum_size)]);
+                b.iter(|| per_bit_atomic(&previous, &row, sum_size, shift));
+            });
+        }
+
+        let previous = to_atomic(&previous);
+        group.bench_with_input(BenchmarkId::new("per_block_atomic", sum_size), &sum_size, |b, &sum_size| {
+            let row = to_atomic(&vec![0; kernel::words_for(sum_size)]);
+            b.iter(|| per_block_atomic(&previous, &row, shift));
+        });
+    }
+
+    group.finish();
+}
+
+fn count_ones(c: &mut Criterion) {
+    let mut group = c.benchmark_group("count_ones");
+
+    for sum_size in [1 << 16, 1 << 24] {
+        let row = sample_row(sum_size);
+        group.throughput(Throughput::Elements(sum_size as u64));
+
+        for kernel in Kernel::ALL.into_iter().filter(|kernel| kernel.is_available()) {
+            group.bench_with_input(BenchmarkId::new(kernel.to_string(), sum_size), &row, |b, row| {
+                b.iter(|| kernel.count_ones(row));
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, row_transition, count_ones);
+criterion_main!(benches);
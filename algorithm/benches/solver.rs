@@ -0,0 +1,176 @@
+//! Whole solves over seeded random datasets, to measure changes to table allocation and
+//! the per-row loops. Throughput is in rows (entries) per second. Before each benchmark
+//! the total bytes one solve allocates (not its peak usage) are printed. The solver's
+//! progress log is switched off, so it isn't part of what is timed.
+
+use std::{alloc::{GlobalAlloc, Layout, System}, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use adder_algorithm::{Bytes, Options, Storage, Strategy};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::ThreadPoolBuilder;
+
+/// Counts every byte allocated, so each solve's allocations can be reported.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size.saturating_sub(layout.size()) as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[derive(Debug, Clone, Copy)]
+enum Signs {
+    Positive,
+    Negative,
+    Mixed,
+}
+
+/// A solve's input, generated from `seed` so every run benchmarks the same data.
+struct Dataset {
+    target: i64,
+    entries: Vec<i64>,
+}
+
+impl Dataset {
+    /// `n` entries whose sums span roughly `sum_size` values.
+    fn new(n: usize, sum_size: usize, signs: Signs, seed: u64) -> Dataset {
+        let mut state = seed | 1;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let largest = (2 * sum_size / n).max(2) as u64;
+        let entries: Vec<i64> = (0..n)
+            .map(|_| {
+                let magnitude = 1 + (next() % largest) as i64;
+                match signs {
+                    Signs::Positive => magnitude,
+                    Signs::Negative => -magnitude,
+                    Signs::Mixed if next() % 2 == 0 => magnitude,
+                    Signs::Mixed => -magnitude,
+                }
+            })
+            .collect();
+
+        // a sum of about half the entries, so the target is usually reachable but
+        // the whole table still has to be filled
+        let target = entries.iter().filter(|_| next() % 2 == 0).sum();
+
+        Dataset { target, entries }
+    }
+
+    fn solve(&self, options: &Options) {
+        adder_algorithm::run_algorithm(self.target, self.entries.clone(), options, None).unwrap();
+    }
+
+    /// Runs one solve and prints how many bytes it allocated.
+    fn report_allocations(&self, id: &str, options: &Options) {
+        let before = ALLOCATED.load(Ordering::Relaxed);
+        self.solve(options);
+        let allocated = ALLOCATED.load(Ordering::Relaxed) - before;
+        println!("{id}: {} allocated per solve", Bytes(allocated));
+    }
+}
+
+fn bench_solve(c: &mut Criterion, name: &str, cases: Vec<(String, Dataset, Options)>) {
+    log::set_max_level(log::LevelFilter::Off);
+
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+
+    for (parameter, dataset, options) in cases {
+        group.throughput(Throughput::Elements(dataset.entries.len() as u64));
+
+        // criterion calls this several times, and not at all when filtered out
+        let mut reported = false;
+        group.bench_with_input(BenchmarkId::from_parameter(&parameter), &dataset, |b, dataset| {
+            if !reported {
+                dataset.report_allocations(&format!("{name}/{parameter}"), &options);
+                reported = true;
+            }
+
+            b.iter(|| dataset.solve(&options));
+        });
+    }
+
+    group.finish();
+}
+
+/// How the solve scales with the number of entries and the width of the rows.
+fn size(c: &mut Criterion) {
+    let mut cases = vec![];
+    for n in [64, 256, 1024] {
+        for sum_size in [1 << 16, 1 << 20] {
+            cases.push((format!("n={n},sum_size={sum_size}"), Dataset::new(n, sum_size, Signs::Mixed, 1), Options::default()));
+        }
+    }
+
+    bench_solve(c, "solve_size", cases);
+}
+
+/// Whether the mix of signs changes anything, e.g. through shift direction.
+fn signs(c: &mut Criterion) {
+    let cases = [Signs::Positive, Signs::Negative, Signs::Mixed].into_iter()
+        .map(|signs| (format!("{signs:?}").to_lowercase(), Dataset::new(256, 1 << 20, signs, 2), Options::default()))
+        .collect();
+
+    bench_solve(c, "solve_signs", cases);
+}
+
+/// How filling rows scales with the size of the thread pool.
+fn threads(c: &mut Criterion) {
+    let available = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut counts = vec![1, 2, 4, available];
+    counts.sort_unstable();
+    counts.dedup();
+
+    let cases = counts.into_iter()
+        .filter(|&threads| threads <= available)
+        .map(|threads| {
+            let pool = ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            let options = Options { thread_pool: Some(Arc::new(pool)), ..Options::default() };
+            (format!("threads={threads}"), Dataset::new(256, 1 << 22, Signs::Mixed, 3), options)
+        })
+        .collect();
+
+    bench_solve(c, "solve_threads", cases);
+}
+
+/// Where the table goes: every strategy with every storage backend.
+fn table(c: &mut Criterion) {
+    let mut cases = vec![];
+    for strategy in Strategy::ALL {
+        for storage in Storage::ALL {
+            let options = Options { strategy: Some(strategy), storage: Some(storage), ..Options::default() };
+            cases.push((format!("{strategy},{storage}"), Dataset::new(512, 1 << 20, Signs::Mixed, 4), options));
+        }
+    }
+
+    bench_solve(c, "table", cases);
+}
+
+criterion_group!(benches, size, signs, threads, table);
+criterion_main!(benches);
@@ -0,0 +1,303 @@
+//! An approximate solver for instances whose sums are too wide for any DP table.
+//!
+//! Instead of one bit per possible sum, each row keeps a sorted list of reachable sums,
+//! trimmed so that no two kept sums are within `delta` of each other. Trimming a row
+//! moves every reachable sum at most `delta` from a kept one, so after `n` rows every
+//! subset sum is within `n × delta` of some kept sum. `delta` is picked from the
+//! requested `epsilon` and the memory budget, and is 0 (an exact search) whenever the
+//! sums are narrow enough.
+
+use std::time::{Duration, Instant};
+
+use crate::{heuristic, Kernel, Solution, Source, Strategy};
+
+/// Marks a parent entry as "took this row's entry"; the remaining bits are the index of
+/// the sum it came from in the previous row.
+const TOOK: u32 = 1 << 31;
+
+/// Bytes each kept sum costs: its parent in the row it belongs to, plus its share of the
+/// two lists being merged. Each row's list of parents also costs its `Vec` itself.
+const BYTES_PER_SUM: u64 = 4;
+const BYTES_PER_LIST_SUM: u64 = 3 * std::mem::size_of::<i128>() as u64;
+const BYTES_PER_ROW: u64 = std::mem::size_of::<Vec<u32>>() as u64;
+
+#[derive(Debug, Clone)]
+pub struct ApproxOptions {
+    /// How far from optimal the answer may be, as a fraction of the sum of the absolute
+    /// values of the entries. Smaller is more accurate but needs more memory: roughly
+    /// `4 × entries² / epsilon` bytes.
+    pub epsilon: f64,
+    /// The most bytes the trimmed lists may occupy. Loosens `epsilon` as needed to fit,
+    /// down to two sums a row; see [`estimate_approx_memory`].
+    /// Unlimited if `None`.
+    pub memory_budget: Option<u64>,
+    /// How long to spend improving the answer by local search once the lists are built.
+    pub polish_for: Duration,
+    /// Stop building the lists and polishing at this time, and answer with the closest
+    /// subset of the entries handled so far. Unlimited if `None`.
+    pub deadline: Option<Instant>,
+}
+
+impl Default for ApproxOptions {
+    fn default() -> Self {
+        ApproxOptions {
+            epsilon: 0.01,
+            memory_budget: None,
+            polish_for: Duration::from_millis(100),
+            deadline: None,
+        }
+    }
+}
+
+/// The closest subset the approximate solver found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Approximation {
+    /// The subset, with [`Source::Approximation`]. No table is filled, so its `strategy`
+    /// and `kernel` are just [`Strategy::Full`] and [`Kernel::Scalar`], and its `sum_size`
+    /// is the most sums a row kept.
+    pub solution: Solution,
+    /// How far the subset's sum is from the target.
+    pub distance: u128,
+    /// No subset is more than this much closer to the target than `solution`. In
+    /// particular, if `distance > error_bound` no subset adds up to the target; if it
+    /// is 0 the answer is exact.
+    pub error_bound: u128,
+    /// How many entries the lists were built over, fewer than all of them if the
+    /// deadline passed first, in which case `error_bound` is just `distance`.
+    pub rows_completed: usize,
+}
+
+impl Approximation {
+    /// Whether this proves that no subset adds up to the target.
+    pub fn target_unreachable(&self) -> bool {
+        self.distance > self.error_bound
+    }
+}
+
+/// The bytes [`approximate`] needs for its lists over `entries` with `options`: at most
+/// the memory budget, unless even two sums a row don't fit in it.
+pub fn estimate_approx_memory(entries: &[i64], options: &ApproxOptions) -> u64 {
+    let n = entries.len() as u64;
+    let delta = delta(entries, options);
+    // sums more than `delta` apart, and no more than there are subsets
+    let sums = (width(entries) / (delta + 1) + 1).min(1u128 << n.min(127)).min(max_sums(entries, options));
+
+    n.saturating_mul(BYTES_PER_ROW)
+        .saturating_add((sums as u64).saturating_mul(BYTES_PER_SUM * n + BYTES_PER_LIST_SUM))
+}
+
+/// Finds a subset of `entries` adding up to close to `target`, in memory independent of
+/// how large the sums are.
+///
+/// The subset's sum always fits in an `i64`: sums that don't are only picked if no other
+/// kept sum is left, and then the empty subset is picked instead.
+pub fn approximate(target: i64, entries: &[i64], options: &ApproxOptions) -> Approximation {
+    let started = Instant::now();
+    let n = entries.len();
+    let delta = delta(entries, options);
+    log::debug!("Approximating with delta={delta}");
+
+    let mut sums = vec![0i128];
+    let mut parents = Vec::with_capacity(n);
+    let mut sum_size = 1;
+    for &entry in entries {
+        if options.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            log::info!("Out of time after {} of {n} entries", parents.len());
+            break;
+        }
+
+        let (next, row) = merge_trimmed(&sums, entry as i128, delta);
+        sums = next;
+        sum_size = sum_size.max(sums.len());
+        parents.push(row);
+    }
+    let rows_completed = parents.len();
+
+    let wide_target = target as i128;
+    let distance_to = |sum: i128| (sum - wide_target).unsigned_abs();
+    let closest_kept = sums.iter().map(|&sum| distance_to(sum)).min().unwrap();
+
+    let mut chosen = vec![false; n];
+    if let Some(mut index) = (0..sums.len()).filter(|&i| i64::try_from(sums[i]).is_ok()).min_by_key(|&i| distance_to(sums[i])) {
+        for (i, row) in parents.iter().enumerate().rev() {
+            chosen[i] = row[index] & TOOK != 0;
+            index = (row[index] & !TOOK) as usize;
+        }
+    }
+
+    if distance_to(heuristic::sum_of(entries, &chosen)) != 0 {
+        let polish_until = Instant::now() + options.polish_for;
+        let polish_until = options.deadline.map_or(polish_until, |deadline| deadline.min(polish_until));
+        let polished = heuristic::improve(entries, target, chosen.clone(), polish_until);
+        if i64::try_from(heuristic::sum_of(entries, &polished)).is_ok() {
+            chosen = polished;
+        }
+    }
+
+    let distance = distance_to(heuristic::sum_of(entries, &chosen));
+    // every subset sum is within `n × delta` of a kept sum, so none is closer than
+    // `closest_kept - n × delta`
+    let error_bound = match rows_completed == n {
+        true => (distance + delta * n as u128).saturating_sub(closest_kept),
+        false => distance,
+    };
+
+    let indices: Vec<usize> = (0..n).filter(|&i| chosen[i]).collect();
+    let amounts: Vec<i64> = indices.iter().map(|&i| entries[i]).collect();
+    let mut solution = Solution {
+        sum: amounts.iter().map(|&amount| amount as i128).sum::<i128>() as i64,
+        indices,
+        amounts,
+        target,
+        source: Source::Approximation,
+        strategy: Strategy::Full,
+        kernel: Kernel::Scalar,
+        elapsed: started.elapsed(),
+        rows: n,
+        sum_size,
+        verified: false,
+    };
+    solution.verified = solution.verify(entries);
+
+    Approximation { solution, distance, error_bound, rows_completed }
+}
+
+fn width(entries: &[i64]) -> u128 {
+    entries.iter().map(|entry| entry.unsigned_abs() as u128).sum()
+}
+
+/// The most sums a row may keep for the lists to fit the memory budget, but at least two.
+fn max_sums(entries: &[i64], options: &ApproxOptions) -> u128 {
+    let n = entries.len() as u64;
+    options.memory_budget
+        .map_or(TOOK as u64, |budget| budget.saturating_sub(n * BYTES_PER_ROW) / (BYTES_PER_SUM * n + BYTES_PER_LIST_SUM))
+        .clamp(2, TOOK as u64) as u128
+}
+
+/// How far apart the sums kept in a row must be, from `options.epsilon` and the budget.
+fn delta(entries: &[i64], options: &ApproxOptions) -> u128 {
+    let width = width(entries);
+    // spacing sums more than `delta` apart fits at most `width / (delta + 1) + 1` of them
+    (width.div_ceil(max_sums(entries, options) - 1).saturating_sub(1))
+        .max((options.epsilon.max(0.0) * width as f64 / entries.len().max(1) as f64) as u128)
+}
+
+/// Merges `sums` with `sums + entry`, keeping only sums more than `delta` above the last
+/// one kept. Returns the new sums and, for each, where it came from.
+fn merge_trimmed(sums: &[i128], entry: i128, delta: u128) -> (Vec<i128>, Vec<u32>) {
+    let mut next = Vec::with_capacity(2 * sums.len());
+    let mut parents = Vec::with_capacity(2 * sums.len());
+
+    let mut keep = |sum: i128, parent: u32| {
+        if next.last().is_none_or(|&last: &i128| sum - last > delta as i128) {
+            next.push(sum);
+            parents.push(parent);
+        }
+    };
+
+    let (mut skip, mut take) = (0, 0);
+    while skip < sums.len() || take < sums.len() {
+        let skipped = sums.get(skip).copied();
+        let taken = sums.get(take).map(|&sum| sum + entry);
+
+        match (skipped, taken) {
+            (Some(skipped), Some(taken)) if taken < skipped => {
+                keep(taken, take as u32 | TOOK);
+                take += 1;
+            }
+            (Some(skipped), _) => {
+                keep(skipped, skip as u32);
+                skip += 1;
+            }
+            (None, Some(taken)) => {
+                keep(taken, take as u32 | TOOK);
+                take += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    // kept for every row, so only as long as it needs to be
+    parents.shrink_to_fit();
+    (next, parents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closest_by_brute_force(target: i64, entries: &[i64]) -> u128 {
+        (0..1u32 << entries.len())
+            .map(|mask| {
+                let sum: i128 = (0..entries.len())
+                    .filter(|i| mask >> i & 1 != 0)
+                    .map(|i| entries[i] as i128)
+                    .sum();
+                (sum - target as i128).unsigned_abs()
+            })
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn narrow_sums_are_exact() {
+        let entries = [3, -7, 12, 5, -1];
+        for target in -10..=25 {
+            let approximation = approximate(target, &entries, &ApproxOptions::default());
+            assert_eq!(approximation.error_bound, 0);
+            assert_eq!(approximation.distance, closest_by_brute_force(target, &entries));
+            assert_eq!(approximation.distance, approximation.solution.sum.abs_diff(target) as u128);
+            assert!(approximation.solution.verified, "{approximation:?}");
+            assert_eq!(approximation.solution.source, Source::Approximation);
+        }
+    }
+
+    #[test]
+    fn wide_sums_stay_within_the_bound() {
+        let entries = [
+            4_000_000_017, -1_234_567_891, 987_654_321_000, 55_555_555_555,
+            -77_777_777_777, 3_141_592_653, 2_718_281_828, 161_803_398_874,
+            -999_999_999, 12_345_678_901, 31_415_926_535, -27_182_818_284,
+        ];
+        let options = ApproxOptions { memory_budget: Some(1 << 12), polish_for: Duration::ZERO, ..ApproxOptions::default() };
+
+        assert!(estimate_approx_memory(&entries, &options) <= 1 << 12);
+
+        for target in [0, 1_000_000_000_000, -50_000_000_000, 123_456_789_012] {
+            let approximation = approximate(target, &entries, &options);
+            assert!(approximation.error_bound > 0);
+            assert!(approximation.distance <= closest_by_brute_force(target, &entries) + approximation.error_bound);
+            assert!(approximation.solution.verified, "{approximation:?}");
+            assert_eq!(approximation.rows_completed, entries.len());
+        }
+
+        // too small for even two sums a row
+        assert!(estimate_approx_memory(&entries, &ApproxOptions { memory_budget: Some(100), ..options }) > 100);
+    }
+
+    #[test]
+    fn a_passed_deadline_answers_from_the_entries_handled() {
+        let entries = [3, -7, 12, 5, -1];
+        let options = ApproxOptions { deadline: Some(Instant::now()), ..ApproxOptions::default() };
+
+        let approximation = approximate(9, &entries, &options);
+        assert_eq!(approximation.rows_completed, 0);
+        assert_eq!(approximation.error_bound, approximation.distance);
+        assert!(!approximation.target_unreachable());
+        assert!(approximation.solution.verified, "{approximation:?}");
+    }
+
+    #[test]
+    fn sums_past_i64_are_never_picked() {
+        let big = 1 << 62;
+        let entries = [big + 1, big + 1];
+        let approximation = approximate(i64::MAX, &entries, &ApproxOptions { polish_for: Duration::ZERO, ..ApproxOptions::default() });
+
+        // both entries add up to 3 off, past i64::MAX, so one of them is as close as it gets
+        assert_eq!(approximation.solution.amounts, [big + 1]);
+        assert!(approximation.solution.verified, "{approximation:?}");
+        assert_eq!(approximation.distance, (i64::MAX - (big + 1)) as u128);
+        // the bound still allows for the sum past i64::MAX
+        assert!(approximation.error_bound >= approximation.distance - 3);
+    }
+}
@@ -0,0 +1,70 @@
+//! Byte counts as people read and write them. The UI model crate compiles this module
+//! in as well, as the web frontend can't depend on this crate, so it only uses std.
+
+use std::{fmt, str::FromStr};
+
+/// A byte count that displays itself in binary units, e.g. `1.5 GiB`, and parses from a
+/// whole number with an optional binary suffix: `K`, `M`, `G` or `T`, e.g. `512M`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bytes(pub u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+        let mut value = self.0 as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit + 1 < UNITS.len() {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            write!(f, "{} {}", self.0, UNITS[0])
+        } else {
+            write!(f, "{value:.1} {}", UNITS[unit])
+        }
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, shift) = match s.char_indices().last() {
+            Some((i, 'k' | 'K')) => (&s[..i], 10),
+            Some((i, 'm' | 'M')) => (&s[..i], 20),
+            Some((i, 'g' | 'G')) => (&s[..i], 30),
+            Some((i, 't' | 'T')) => (&s[..i], 40),
+            _ => (s, 0),
+        };
+
+        number.trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(1 << shift))
+            .map(Bytes)
+            .ok_or_else(|| format!("invalid byte count `{s}`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_suffixes_and_displays_binary_units() {
+        for (s, bytes) in [("0", 0), ("1500", 1500), (" 2k ", 2048), ("512M", 512 << 20), ("8 G", 8 << 30), ("1t", 1 << 40)] {
+            assert_eq!(s.parse(), Ok(Bytes(bytes)), "{s}");
+        }
+        for s in ["", "M", "-1", "1.5G", "12Q", "17000000T"] {
+            assert!(s.parse::<Bytes>().is_err(), "{s}");
+        }
+
+        assert_eq!(Bytes(1023).to_string(), "1023 B");
+        assert_eq!(Bytes(1536).to_string(), "1.5 KiB");
+        assert_eq!(Bytes(8 << 30).to_string(), "8.0 GiB");
+        assert_eq!(Bytes(u64::MAX).to_string(), "16384.0 PiB");
+    }
+}
@@ -0,0 +1,341 @@
+//! Saving a solve's rows to disk as it goes, so a crashed or interrupted solve can be
+//! resumed instead of started over.
+//!
+//! A checkpoint file is a header describing the problem followed by one record per saved
+//! row, all little-endian:
+//!
+//! - header: magic `ADDERCKP`, version `u32`, target `i64`, zero index `u64`, strategy
+//!   `u8`, entry count `u64`, the entries as `i64`s, words per row `u64`
+//! - record: row index `u64`, the row's words, CRC-32 of the index and words `u32`
+//!
+//! The full strategy saves every row, the low-memory strategy only the rows it keeps.
+//! Records are only ever appended and are synced to disk at every checkpoint, so a crash
+//! leaves at worst a partial record at the end, which is dropped when resuming.
+
+use std::{fs::{File, OpenOptions}, io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+
+use crate::{dimensions, kernel, low_memory_stride, SolveError, Strategy};
+
+const MAGIC: &[u8; 8] = b"ADDERCKP";
+const VERSION: u32 = 1;
+
+/// Where and how often to save a solve's progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointOptions {
+    /// The checkpoint file. A new solve overwrites it, a resumed one appends to it.
+    pub path: PathBuf,
+    /// Save once at least this many rows have been filled since the last save. With
+    /// the low-memory strategy this is rounded up to the next row it keeps.
+    pub every: usize,
+}
+
+/// The problem a checkpoint file was saved from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointHeader {
+    pub target: i64,
+    pub entries: Vec<i64>,
+    pub strategy: Strategy,
+}
+
+impl CheckpointHeader {
+    /// Reads the header of the checkpoint file at `path`.
+    pub fn read(path: &Path) -> Result<CheckpointHeader, SolveError> {
+        let mut reader = BufReader::new(File::open(path).map_err(|err| error(path, err))?);
+        read_header(&mut reader).map_err(|err| error(path, err))
+    }
+}
+
+/// Appends rows to a checkpoint file.
+pub(crate) struct Writer {
+    file: BufWriter<File>,
+    path: PathBuf,
+    every: usize,
+    /// Rows between saved records: 1 for the full strategy, the kept-row stride for low-memory.
+    stride: usize,
+    saved_records: usize,
+    saved_rows: usize,
+}
+
+impl Writer {
+    /// Creates (or truncates) the checkpoint file and writes `header` to it.
+    pub(crate) fn create(options: &CheckpointOptions, header: &CheckpointHeader) -> Result<Writer, SolveError> {
+        let path = &options.path;
+        let mut file = BufWriter::new(File::create(path).map_err(|err| error(path, err))?);
+        write_header(&mut file, header)
+            .and_then(|()| sync(&mut file))
+            .map_err(|err| error(path, err))?;
+
+        Ok(Writer {
+            file,
+            path: path.clone(),
+            every: options.every.max(1),
+            stride: stride(header),
+            saved_records: 0,
+            saved_rows: 0,
+        })
+    }
+
+    /// Saves the records not yet in the file if at least `every` rows have been filled
+    /// since the last save. There are `records` rows this strategy saves so far, and
+    /// `record(k)` is the `k`th of them; `rows_completed` is how many rows have been filled.
+    pub(crate) fn maybe_save<R: AsRef<[u64]>>(&mut self, records: usize, record: impl Fn(usize) -> R, rows_completed: usize) {
+        if rows_completed - self.saved_rows < self.every {
+            return;
+        }
+
+        match self.save(records, record) {
+            Ok(saved_records) => {
+                log::debug!("Saved a checkpoint after {rows_completed} rows to {}", self.path.display());
+                self.saved_records = saved_records;
+                self.saved_rows = rows_completed;
+            }
+            Err(err) => log::warn!("Could not save a checkpoint to {}: {err}", self.path.display()),
+        }
+    }
+
+    /// Appends the records not yet saved, returning how many records the file now holds.
+    fn save<R: AsRef<[u64]>>(&mut self, records: usize, record: impl Fn(usize) -> R) -> io::Result<usize> {
+        let mut saved_records = self.saved_records;
+        for k in self.saved_records..records {
+            let row = record(k);
+            let row = row.as_ref();
+            let mut body = Vec::with_capacity(8 * (1 + row.len()));
+            body.extend_from_slice(&(((k + 1) * self.stride - 1) as u64).to_le_bytes());
+            for word in row {
+                body.extend_from_slice(&word.to_le_bytes());
+            }
+
+            self.file.write_all(&body)?;
+            self.file.write_all(&crc32fast::hash(&body).to_le_bytes())?;
+            saved_records = k + 1;
+        }
+
+        sync(&mut self.file)?;
+        Ok(saved_records)
+    }
+}
+
+/// A checkpoint file opened to carry on from, with its header read but none of its rows.
+pub(crate) struct Resume {
+    pub(crate) header: CheckpointHeader,
+    reader: BufReader<File>,
+    options: CheckpointOptions,
+}
+
+impl Resume {
+    /// Opens the checkpoint file and reads its header.
+    pub(crate) fn open(options: &CheckpointOptions) -> Result<Resume, SolveError> {
+        let path = &options.path;
+        let mut reader = BufReader::new(File::open(path).map_err(|err| error(path, err))?);
+        let header = read_header(&mut reader).map_err(|err| error(path, err))?;
+
+        Ok(Resume { header, reader, options: options.clone() })
+    }
+
+    /// Reads the saved records one at a time, passing the `k`th record's row to
+    /// `restore(k, row)`, so no more than one row is held here at once. Returns how many
+    /// records were intact and a writer appending after the last of them.
+    pub(crate) fn restore(self, mut restore: impl FnMut(usize, &[u64])) -> Result<(usize, Writer), SolveError> {
+        let Resume { header, mut reader, options } = self;
+        let path = &options.path;
+        let wrap = |err| error(path, err);
+
+        let (_, sum_size) = dimensions(&header.entries)?;
+        let words = kernel::words_for(sum_size);
+        let stride = stride(&header);
+
+        let mut valid_len = reader.stream_position().map_err(wrap)?;
+        let mut records = 0;
+        let mut record = vec![0; 8 * (1 + words) + 4];
+        let mut row = vec![0; words];
+        loop {
+            match reader.read_exact(&mut record) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(wrap(err)),
+            }
+
+            let (body, checksum) = record.split_at(record.len() - 4);
+            let index = u64::from_le_bytes(body[..8].try_into().unwrap()) as usize;
+            if crc32fast::hash(body).to_le_bytes() != checksum || index != (records + 1) * stride - 1 {
+                break;
+            }
+
+            for (word, bytes) in row.iter_mut().zip(body[8..].chunks_exact(8)) {
+                *word = u64::from_le_bytes(bytes.try_into().unwrap());
+            }
+            restore(records, &row);
+            records += 1;
+            valid_len += record.len() as u64;
+        }
+        drop(reader);
+
+        let file = OpenOptions::new().write(true).open(path).map_err(wrap)?;
+        file.set_len(valid_len).map_err(wrap)?;
+        let mut file = BufWriter::new(file);
+        file.seek(SeekFrom::End(0)).map_err(wrap)?;
+
+        let writer = Writer {
+            file,
+            path: path.clone(),
+            every: options.every.max(1),
+            stride,
+            saved_records: records,
+            saved_rows: records * stride,
+        };
+
+        Ok((records, writer))
+    }
+}
+
+/// Where a solve saves its progress: a new checkpoint file, or one being resumed whose
+/// saved rows have yet to be read back.
+pub(crate) enum Checkpoint {
+    Created(Writer),
+    Resumed(Resume),
+}
+
+/// Restores the rows saved in `checkpoint`, if it is being resumed, through
+/// `restore(k, row)`. Returns how many there were and the writer to carry on saving with.
+pub(crate) fn restore(checkpoint: Option<Checkpoint>, restore: impl FnMut(usize, &[u64])) -> Result<(usize, Option<Writer>), SolveError> {
+    match checkpoint {
+        None => Ok((0, None)),
+        Some(Checkpoint::Created(writer)) => Ok((0, Some(writer))),
+        Some(Checkpoint::Resumed(resume)) => {
+            let (records, writer) = resume.restore(restore)?;
+            Ok((records, Some(writer)))
+        }
+    }
+}
+
+fn stride(header: &CheckpointHeader) -> usize {
+    match header.strategy {
+        Strategy::Full => 1,
+        Strategy::LowMemory => low_memory_stride(header.entries.len()),
+    }
+}
+
+fn sync(file: &mut BufWriter<File>) -> io::Result<()> {
+    file.flush()?;
+    file.get_ref().sync_data()
+}
+
+fn error(path: &Path, err: io::Error) -> SolveError {
+    SolveError::Checkpoint(format!("{}: {err}", path.display()))
+}
+
+fn write_header(out: &mut impl Write, header: &CheckpointHeader) -> io::Result<()> {
+    let (zero_index, sum_size) = dimensions(&header.entries)
+        .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err.to_string()))?;
+    let strategy = match header.strategy {
+        Strategy::Full => 0u8,
+        Strategy::LowMemory => 1,
+    };
+
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&header.target.to_le_bytes())?;
+    out.write_all(&(zero_index as u64).to_le_bytes())?;
+    out.write_all(&[strategy])?;
+    out.write_all(&(header.entries.len() as u64).to_le_bytes())?;
+    for entry in &header.entries {
+        out.write_all(&entry.to_le_bytes())?;
+    }
+    out.write_all(&(kernel::words_for(sum_size) as u64).to_le_bytes())
+}
+
+fn read_header(input: &mut impl Read) -> io::Result<CheckpointHeader> {
+    let invalid = |what: &str| io::Error::new(ErrorKind::InvalidData, format!("not a valid checkpoint file ({what})"));
+
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("bad magic number"));
+    }
+    if read_u32(input)? != VERSION {
+        return Err(invalid("unsupported version"));
+    }
+
+    let target = read_u64(input)? as i64;
+    let zero_index = read_u64(input)?;
+
+    let mut strategy = [0];
+    input.read_exact(&mut strategy)?;
+    let strategy = match strategy[0] {
+        0 => Strategy::Full,
+        1 => Strategy::LowMemory,
+        _ => return Err(invalid("unknown strategy")),
+    };
+
+    let n = read_u64(input)?;
+    let entries = (0..n)
+        .map(|_| read_u64(input).map(|entry| entry as i64))
+        .collect::<io::Result<Vec<_>>>()?;
+    let words = read_u64(input)?;
+
+    let (expected_zero_index, sum_size) = dimensions(&entries).map_err(|_| invalid("entries too wide"))?;
+    if zero_index != expected_zero_index as u64 || words != kernel::words_for(sum_size) as u64 {
+        return Err(invalid("row size doesn't match the entries"));
+    }
+
+    Ok(CheckpointHeader { target, entries, strategy })
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resume(options: &CheckpointOptions) -> (CheckpointHeader, Vec<Vec<u64>>, Writer) {
+        let resume = Resume::open(options).unwrap();
+        let header = resume.header.clone();
+        let mut rows = vec![];
+        let (records, writer) = resume.restore(|k, row| {
+            assert_eq!(k, rows.len());
+            rows.push(row.to_vec());
+        }).unwrap();
+        assert_eq!(records, rows.len());
+
+        (header, rows, writer)
+    }
+
+    #[test]
+    fn resumes_from_the_last_intact_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        let options = CheckpointOptions { path: path.clone(), every: 2 };
+        let header = CheckpointHeader { target: 5, entries: vec![3, -2, 70, 4], strategy: Strategy::Full };
+        let rows: Vec<Vec<u64>> = (1..=3).map(|row| vec![row; 2]).collect();
+
+        let mut writer = Writer::create(&options, &header).unwrap();
+        for saved in 1..=3 {
+            writer.maybe_save(saved, |k| &rows[k], saved);
+        }
+        drop(writer);
+
+        let (read, restored, _) = resume(&options);
+        assert_eq!(read, header);
+        assert_eq!(restored, &rows[..2]);
+
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 1).unwrap();
+        let (_, restored, mut writer) = resume(&options);
+        assert_eq!(restored, &rows[..1]);
+
+        writer.maybe_save(rows.len(), |k| &rows[k], 3);
+        drop(writer);
+        let (_, restored, _) = resume(&options);
+        assert_eq!(restored, rows);
+    }
+}
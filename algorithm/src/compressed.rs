@@ -0,0 +1,314 @@
+//! Rows stored compressed, in the spirit of Roaring bitmaps: each row is cut into chunks
+//! of 2^16 sums, and each chunk is kept as whichever of a sorted list of its set bits, a
+//! list of its runs of set bits, or its plain words is smallest.
+//!
+//! Rows usually start and end with long stretches of unreachable or reachable sums, which
+//! cost next to nothing as runs, around a dense middle kept as plain words. Rows are filled
+//! uncompressed and compressed once full, so only the last row filled is held whole.
+
+use std::borrow::Cow;
+
+use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator, ParallelSlice, ParallelSliceMut};
+
+use crate::kernel;
+
+/// Words per chunk: 2^16 bits, so offsets within a chunk fit in a `u16`.
+const CHUNK_WORDS: usize = 1024;
+
+/// One chunk of a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Container {
+    /// The offsets of the set bits, in increasing order.
+    Array(Box<[u16]>),
+    /// The first and last offsets of each run of set bits, in increasing order.
+    Runs(Box<[(u16, u16)]>),
+    /// The chunk's words as they are.
+    Bitmap(Box<[u64]>),
+}
+
+impl Container {
+    fn compress(words: &[u64]) -> Container {
+        let ones: usize = words.iter().map(|word| word.count_ones() as usize).sum();
+
+        // a run starts at every set bit whose lower neighbour is clear
+        let mut carry = 0;
+        let mut runs = 0;
+        for &word in words {
+            runs += (word & !(word << 1 | carry)).count_ones() as usize;
+            carry = word >> 63;
+        }
+
+        let array_bytes = ones * size_of::<u16>();
+        let runs_bytes = runs * size_of::<(u16, u16)>();
+        let bitmap_bytes = size_of_val(words);
+
+        if array_bytes <= runs_bytes && array_bytes <= bitmap_bytes {
+            let offsets = std::iter::successors(kernel::next_one(words, 0), |&offset| kernel::next_one(words, offset + 1));
+            Container::Array(offsets.map(|offset| offset as u16).collect())
+        } else if runs_bytes <= bitmap_bytes {
+            let mut runs = Vec::with_capacity(runs);
+            let mut from = 0;
+            while let Some(first) = kernel::next_one(words, from) {
+                let end = kernel::next_zero(words, first).unwrap_or(words.len() * 64);
+                runs.push((first as u16, (end - 1) as u16));
+                from = end;
+            }
+            Container::Runs(runs.into())
+        } else {
+            Container::Bitmap(words.into())
+        }
+    }
+
+    /// Writes the chunk out to `words`, which must be as long as the chunk it came from.
+    fn decompress_into(&self, words: &mut [u64]) {
+        match self {
+            Container::Array(offsets) => {
+                words.fill(0);
+                for &offset in offsets.iter() {
+                    kernel::set(words, offset as usize);
+                }
+            }
+            Container::Runs(runs) => {
+                words.fill(0);
+                for &(first, last) in runs.iter() {
+                    set_range(words, first as usize, last as usize + 1);
+                }
+            }
+            Container::Bitmap(bitmap) => words.copy_from_slice(bitmap),
+        }
+    }
+
+    fn get(&self, offset: usize) -> bool {
+        match self {
+            Container::Array(offsets) => offsets.binary_search(&(offset as u16)).is_ok(),
+            Container::Runs(runs) => {
+                let after = runs.partition_point(|&(first, _)| first as usize <= offset);
+                after > 0 && runs[after - 1].1 as usize >= offset
+            }
+            Container::Bitmap(bitmap) => kernel::get(bitmap, offset),
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Container::Array(offsets) => size_of_val(&**offsets),
+            Container::Runs(runs) => size_of_val(&**runs),
+            Container::Bitmap(bitmap) => size_of_val(&**bitmap),
+        }
+    }
+}
+
+/// Sets bits `start..end` of `words`. Like the vendored
+/// `AtomicBitVec::set_range`, for plain words; the tests check both.
+fn set_range(words: &mut [u64], start: usize, end: usize) {
+    let (first, last) = (start / 64, (end - 1) / 64);
+    for (w, word) in words.iter_mut().enumerate().take(last + 1).skip(first) {
+        let low = if w == first { u64::MAX << (start % 64) } else { u64::MAX };
+        let high = if w == last { u64::MAX >> (63 - (end - 1) % 64) } else { u64::MAX };
+        *word |= low & high;
+    }
+}
+
+/// A row of `words` words, compressed chunk by chunk. A row with no chunks at all has no
+/// bits set, which is how every row starts out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CompressedRow {
+    chunks: Vec<Container>,
+}
+
+impl CompressedRow {
+    pub(crate) fn compress(row: &[u64]) -> CompressedRow {
+        CompressedRow { chunks: row.par_chunks(CHUNK_WORDS).map(Container::compress).collect() }
+    }
+
+    /// Writes the row out to `row`, which must be as long as the row it came from.
+    pub(crate) fn decompress_into(&self, row: &mut [u64]) {
+        if self.chunks.is_empty() {
+            row.fill(0);
+            return;
+        }
+
+        row.par_chunks_mut(CHUNK_WORDS)
+            .zip(self.chunks.par_iter())
+            .for_each(|(words, chunk)| chunk.decompress_into(words));
+    }
+
+    pub(crate) fn get(&self, index: usize) -> bool {
+        self.chunks.get(index / (CHUNK_WORDS * 64))
+            .is_some_and(|chunk| chunk.get(index % (CHUNK_WORDS * 64)))
+    }
+
+    /// The bytes the row takes up, including the list of chunks.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        size_of_val(&*self.chunks) + self.chunks.iter().map(Container::heap_bytes).sum::<usize>()
+    }
+}
+
+/// A fixed number of compressed rows of `words` words each, all starting out cleared.
+pub(crate) struct CompressedTable {
+    rows: Vec<CompressedRow>,
+    words: usize,
+    /// The last row filled and its words, so the row after it can be filled straight from
+    /// them instead of decompressing it again.
+    last: Option<(usize, Vec<u64>)>,
+    /// A row's worth of words to fill the next row in.
+    spare: Vec<u64>,
+    /// The sum of the rows' [`heap_bytes`](CompressedRow::heap_bytes), kept up to date
+    /// as rows are written so the table's size can be checked after every row.
+    row_bytes: usize,
+}
+
+impl CompressedTable {
+    pub(crate) fn new(words: usize, rows: usize) -> CompressedTable {
+        CompressedTable { rows: vec![CompressedRow::default(); rows], words, last: None, spare: vec![], row_bytes: 0 }
+    }
+
+    pub(crate) fn row(&self, i: usize) -> Cow<'_, [u64]> {
+        match &self.last {
+            Some((last, words)) if *last == i => Cow::Borrowed(words),
+            _ => Cow::Owned(self.decompress(i)),
+        }
+    }
+
+    pub(crate) fn get(&self, i: usize, index: usize) -> bool {
+        self.rows[i].get(index)
+    }
+
+    pub(crate) fn set_row(&mut self, i: usize, row: &[u64]) {
+        self.replace(i, CompressedRow::compress(row));
+        if self.last.as_ref().is_some_and(|(last, _)| *last == i) {
+            self.last = None;
+        }
+    }
+
+    /// Calls `f` with row `i - 1`, if there is one, and a cleared row to fill as row `i`,
+    /// then compresses the filled row.
+    pub(crate) fn fill_row(&mut self, i: usize, f: impl FnOnce(Option<&[u64]>, &mut [u64])) {
+        let previous = i.checked_sub(1).map(|previous| match self.last.take() {
+            Some((last, words)) if last == previous => words,
+            _ => self.decompress(previous),
+        });
+
+        let mut row = std::mem::take(&mut self.spare);
+        row.clear();
+        row.resize(self.words, 0);
+        f(previous.as_deref(), &mut row);
+
+        self.replace(i, CompressedRow::compress(&row));
+        self.last = Some((i, row));
+        self.spare = previous.unwrap_or_default();
+    }
+
+    /// The bytes the compressed rows take up, not counting the rows held whole for filling.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.rows.len() * size_of::<CompressedRow>() + self.row_bytes
+    }
+
+    fn replace(&mut self, i: usize, row: CompressedRow) {
+        self.row_bytes = self.row_bytes - self.rows[i].heap_bytes() + row.heap_bytes();
+        self.rows[i] = row;
+    }
+
+    fn decompress(&self, i: usize) -> Vec<u64> {
+        let mut words = vec![0; self.words];
+        self.rows[i].decompress_into(&mut words);
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use atomic_bitvec::BitVec;
+
+    use super::*;
+
+    /// A row of `words` words with `f(index)` set at each index.
+    fn row_where(words: usize, f: impl Fn(usize) -> bool) -> Vec<u64> {
+        let mut row = vec![0; words];
+        for index in (0..words * 64).filter(|&index| f(index)) {
+            kernel::set(&mut row, index);
+        }
+        row
+    }
+
+    #[test]
+    fn rows_survive_compression() {
+        let words = CHUNK_WORDS * 3 + 17;
+        let bits = words * 64;
+        let rows = [
+            ("empty", row_where(words, |_| false)),
+            ("full", row_where(words, |_| true)),
+            ("sparse", row_where(words, |index| index % 1009 == 3)),
+            // unreachable, then reachable, edges around a dense middle
+            ("dp-like", row_where(words, |index| index > bits / 5 && (index < bits * 4 / 5 || index % 3 == 0 || index > bits - 700))),
+            ("chunk edges", row_where(words, |index| (index + 1) % (CHUNK_WORDS * 64) < 2)),
+        ];
+
+        for (name, row) in &rows {
+            let compressed = CompressedRow::compress(row);
+            let mut words_out = vec![u64::MAX; words];
+            compressed.decompress_into(&mut words_out);
+            assert_eq!(&words_out, row, "{name}");
+
+            for index in (0..bits).step_by(97).chain([0, 65535, 65536, bits - 1]) {
+                assert_eq!(compressed.get(index), kernel::get(row, index), "{name} at {index}");
+            }
+
+            // never much bigger than the words themselves
+            assert!(compressed.heap_bytes() <= words * 8 + compressed.chunks.len() * 32, "{name}");
+        }
+
+        let dp_like = CompressedRow::compress(&rows[3].1);
+        assert!(dp_like.heap_bytes() < words * 8 * 3 / 4, "{}", dp_like.heap_bytes());
+        assert!(CompressedRow::compress(&rows[1].1).heap_bytes() < 256);
+    }
+
+    #[test]
+    fn set_range_agrees_with_atomic_bitvec_and_bit_by_bit() {
+        let words = 4;
+        let base = row_where(words, |index| index % 5 == 0);
+        for start in [0, 1, 63, 64, 65, 100, 200, 255] {
+            for end in [start + 1, start + 2, 64, 65, 128, 129, 191, 256].into_iter().filter(|&end| end > start && end <= words * 64) {
+                let mut row = base.clone();
+                set_range(&mut row, start, end);
+                let expected = row_where(words, |index| (start..end).contains(&index) || kernel::get(&base, index));
+                assert_eq!(row, expected, "{start}..{end}");
+
+                let vendored = BitVec::from_blocks(base.clone(), words * 64).into_atomic();
+                vendored.set_range(start..end, Ordering::AcqRel);
+                assert_eq!(vendored.into_frozen().as_blocks(), row, "{start}..{end}");
+            }
+        }
+    }
+
+    #[test]
+    fn filling_goes_through_the_last_row() {
+        let mut table = CompressedTable::new(CHUNK_WORDS + 1, 4);
+        assert_eq!(*table.row(3), [0; CHUNK_WORDS + 1]);
+
+        table.fill_row(0, |previous, row| {
+            assert!(previous.is_none());
+            row[0] = 1;
+            row[CHUNK_WORDS] = 2;
+        });
+        for i in 1..4 {
+            table.fill_row(i, |previous, row| {
+                for (word, previous) in row.iter_mut().zip(previous.unwrap()) {
+                    *word = previous << 1 | previous;
+                }
+            });
+        }
+
+        assert_eq!(table.row(3)[..1], [0b1111]);
+        assert_eq!(table.row(1)[CHUNK_WORDS], 0b110);
+        assert!(table.get(2, CHUNK_WORDS * 64 + 3) && !table.get(2, CHUNK_WORDS * 64 + 4));
+
+        table.set_row(3, &vec![u64::MAX; CHUNK_WORDS + 1]);
+        assert!(table.row(3).iter().all(|&word| word == u64::MAX));
+
+        let recounted: usize = table.rows.iter().map(|row| size_of::<CompressedRow>() + row.heap_bytes()).sum();
+        assert_eq!(table.heap_bytes(), recounted);
+    }
+}
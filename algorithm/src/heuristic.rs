@@ -0,0 +1,114 @@
+//! A quick approximate search, used when the exact DP runs out of time and to polish
+//! the answers of the [approximate solver](crate::approximate).
+
+use std::time::Instant;
+
+/// Looks for a subset of `entries` adding up to as close to `target` as possible,
+/// stopping at `deadline` or as soon as it hits `target` exactly.
+///
+/// Starts from a greedy pick (largest entries first, taking each one that brings the
+/// sum closer) and then [improves](improve) it.
+///
+/// Returns whether each entry is in the subset.
+pub(crate) fn closest_subset(entries: &[i64], target: i64, deadline: Instant) -> Vec<bool> {
+    improve(entries, target, greedy(entries, target as i128), deadline)
+}
+
+/// Improves the subset picked by `chosen` by randomised local search until `deadline`
+/// or until it adds up to exactly `target`: flipping single entries in or out,
+/// swapping pairs, and restarting from a perturbed copy of the best pick so far
+/// whenever it gets stuck. Never returns a pick further from `target` than `chosen`.
+pub(crate) fn improve(entries: &[i64], target: i64, mut chosen: Vec<bool>, deadline: Instant) -> Vec<bool> {
+    let n = entries.len();
+    let target = target as i128;
+    let error = |sum: i128| (sum - target).unsigned_abs();
+    let delta = |chosen: &[bool], i: usize| if chosen[i] { -(entries[i] as i128) } else { entries[i] as i128 };
+
+    let mut sum = sum_of(entries, &chosen);
+
+    let mut best = chosen.clone();
+    let mut best_error = error(sum);
+
+    let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+    let mut stale = 0;
+
+    for iteration in 0u64.. {
+        if best_error == 0 || n == 0 || (iteration % 1024 == 0 && Instant::now() >= deadline) {
+            break;
+        }
+
+        let i = rng.below(n);
+        let flip = delta(&chosen, i);
+
+        if error(sum + flip) < error(sum) {
+            chosen[i] = !chosen[i];
+            sum += flip;
+        } else {
+            let j = rng.below(n);
+            let swap = flip + delta(&chosen, j);
+
+            if chosen[i] != chosen[j] && error(sum + swap) < error(sum) {
+                chosen[i] = !chosen[i];
+                chosen[j] = !chosen[j];
+                sum += swap;
+            } else {
+                stale += 1;
+            }
+        }
+
+        if error(sum) < best_error {
+            best.clone_from(&chosen);
+            best_error = error(sum);
+            stale = 0;
+        }
+
+        if stale > 4 * n {
+            chosen.clone_from(&best);
+            for _ in 0..(1 + n / 20).min(8) {
+                let k = rng.below(n);
+                chosen[k] = !chosen[k];
+            }
+            sum = sum_of(entries, &chosen);
+            stale = 0;
+        }
+    }
+
+    best
+}
+
+fn greedy(entries: &[i64], target: i128) -> Vec<bool> {
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(entries[i].unsigned_abs()));
+
+    let mut chosen = vec![false; entries.len()];
+    let mut sum = 0i128;
+    for i in order {
+        let with = sum + entries[i] as i128;
+        if (with - target).unsigned_abs() < (sum - target).unsigned_abs() {
+            chosen[i] = true;
+            sum = with;
+        }
+    }
+
+    chosen
+}
+
+pub(crate) fn sum_of(entries: &[i64], chosen: &[bool]) -> i128 {
+    entries.iter()
+        .zip(chosen)
+        .filter(|(_, &chosen)| chosen)
+        .map(|(&entry, _)| entry as i128)
+        .sum()
+}
+
+/// A small, fixed-seed PRNG so searches are reproducible.
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
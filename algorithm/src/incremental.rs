@@ -0,0 +1,285 @@
+//! A solver that keeps its table between solves, for when the entries change a little
+//! at a time.
+//!
+//! Row `i` only depends on the first `i + 1` entries, so appending entries just fills
+//! new rows, and removing one only refills the rows from it on. The target only matters
+//! when backtracking, so changing it refills nothing. Rows share one frame of sums,
+//! which grows with headroom (as far as the memory budget allows) as entries push sums
+//! past it, moving the existing rows into the wider frame rather than refilling them.
+
+use std::time::Instant;
+
+use crate::{dimensions, estimate_memory, index_of, kernel, kernel_for, memory, with_pool, Kernel, Observer, Options, ReachableSums, Solution, SolveError, Source, Solver, Strategy};
+
+/// Holds every row of the table for its entries, always in memory, like
+/// [`Strategy::Full`].
+pub struct IncrementalSolver {
+    entries: Vec<i64>,
+    rows: Vec<Vec<u64>>,
+    target: i64,
+    zero_index: usize,
+    sum_size: usize,
+    kernel: Kernel,
+    options: Options,
+}
+
+impl IncrementalSolver {
+    /// A solver with no entries yet. Of `options`, only the kernel, the memory budget
+    /// and the thread pool apply: the table is always kept whole and in memory, and
+    /// filled to the last row.
+    pub fn new(target: i64, options: &Options) -> Result<IncrementalSolver, SolveError> {
+        Ok(IncrementalSolver {
+            entries: vec![],
+            rows: vec![],
+            target,
+            zero_index: 0,
+            sum_size: 1,
+            kernel: kernel_for(options)?,
+            options: options.clone(),
+        })
+    }
+
+    pub fn entries(&self) -> &[i64] {
+        &self.entries
+    }
+
+    pub fn target(&self) -> i64 {
+        self.target
+    }
+
+    /// Changes the target. Nothing is refilled.
+    pub fn set_target(&mut self, target: i64) {
+        self.target = target;
+    }
+
+    /// Appends `entries`, filling one new row for each. On error nothing changes.
+    pub fn extend(&mut self, entries: impl IntoIterator<Item = i64>, observer: Option<&dyn Observer>) -> Result<(), SolveError> {
+        self.refill(self.entries.len(), entries.into_iter().collect(), observer)
+    }
+
+    /// Removes and returns the entry at `index`, refilling the rows after it. On error
+    /// nothing changes.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize, observer: Option<&dyn Observer>) -> Result<i64, SolveError> {
+        let removed = self.entries[index];
+        let tail = self.entries[index + 1..].to_vec();
+        self.refill(index, tail, observer)?;
+
+        Ok(removed)
+    }
+
+    /// Replaces the entries with `entries`, keeping the rows of the longest prefix they
+    /// share with the current ones. Returns how many rows were kept. On error nothing
+    /// changes.
+    pub fn set_entries(&mut self, entries: Vec<i64>, observer: Option<&dyn Observer>) -> Result<usize, SolveError> {
+        let kept = self.entries.iter()
+            .zip(&entries)
+            .take_while(|(current, new)| current == new)
+            .count();
+
+        if kept < self.entries.len() || kept < entries.len() {
+            log::debug!("Keeping {kept} of {} rows", self.entries.len());
+            self.refill(kept, entries[kept..].to_vec(), observer)?;
+        }
+
+        Ok(kept)
+    }
+
+    /// Every sum some subset of the entries adds up to, from the last row.
+    pub fn reachable_sums(&self) -> ReachableSums {
+        let row = self.rows.last().cloned().unwrap_or_else(|| {
+            let mut row = vec![0; kernel::words_for(self.sum_size)];
+            kernel::set(&mut row, self.zero_index);
+            row
+        });
+
+        ReachableSums::new(row, self.zero_index, self.sum_size)
+    }
+
+    /// Backtracks the table for a subset adding up to the target, or `None` if there is
+    /// none.
+    pub fn solve(&self) -> Option<Solution> {
+        let started = Instant::now();
+        let target_index = index_of(self.target, self.zero_index, self.sum_size)?;
+        let solver = self.solver(target_index, started);
+
+        let Some(last_row) = self.rows.last() else {
+            // only the empty subset, adding up to 0
+            return (target_index == self.zero_index).then(|| solver.solution(vec![], Source::Table));
+        };
+
+        if !kernel::get(last_row, target_index) {
+            return None;
+        }
+
+        let indices = solver.backtrack(self.rows.len() - 1, target_index, |row, sum| kernel::get(&self.rows[row], sum));
+        Some(solver.solution(indices, Source::Table))
+    }
+
+    /// Replaces every entry from `keep` on with `tail` and fills their rows, first
+    /// widening the frame if the new entries' sums don't fit in it.
+    fn refill(&mut self, keep: usize, tail: Vec<i64>, observer: Option<&dyn Observer>) -> Result<(), SolveError> {
+        let entries: Vec<i64> = self.entries[..keep].iter().copied().chain(tail).collect();
+        let (zero_index, sum_size) = self.frame_within_budget(&entries)?;
+
+        // the pool is built before anything changes, so failing to build it changes nothing
+        let options = self.options.clone();
+        with_pool(&options, || self.fill(keep, entries, zero_index, sum_size, observer))
+    }
+
+    /// Moves the first `keep` rows into the new frame, then fills the rows for the rest
+    /// of `entries`.
+    fn fill(&mut self, keep: usize, entries: Vec<i64>, zero_index: usize, sum_size: usize, observer: Option<&dyn Observer>) {
+        let words = kernel::words_for(sum_size);
+
+        self.rows.truncate(keep);
+        if (zero_index, sum_size) != (self.zero_index, self.sum_size) {
+            log::debug!("Widening the rows from {} to {sum_size} sums", self.sum_size);
+            let shift = zero_index as isize - self.zero_index as isize;
+            for row in &mut self.rows {
+                *row = kernel::resized(row, words, shift);
+            }
+        }
+
+        self.entries = entries;
+        self.zero_index = zero_index;
+        self.sum_size = sum_size;
+
+        let mut rows = std::mem::take(&mut self.rows);
+        let solver = Solver { observer, ..self.solver(0, Instant::now()) };
+        for i in keep..solver.entries.len() {
+            solver.report_progress(i);
+
+            let mut row = vec![0; words];
+            solver.fill_row(&mut row, rows.last().map(Vec::as_slice), solver.entries[i]);
+            rows.push(row);
+        }
+
+        self.rows = rows;
+    }
+
+    /// The frame rows over `entries` need: the current one if every sum fits in it,
+    /// otherwise one at least twice as wide on each side that overflows, so that a run
+    /// of appends only rarely moves the rows.
+    fn frame_for(&self, entries: &[i64]) -> Result<(usize, usize), SolveError> {
+        let (needed_low, needed_size) = dimensions(entries)?;
+        let needed_high = needed_size - 1 - needed_low;
+        let (low, high) = (self.zero_index, self.sum_size - 1 - self.zero_index);
+
+        if needed_low <= low && needed_high <= high {
+            return Ok((self.zero_index, self.sum_size));
+        }
+
+        let grow = |needed: usize, have: usize| if needed > have { needed.max(have.saturating_mul(2)) } else { have };
+        let (low, high) = (grow(needed_low, low), grow(needed_high, high));
+
+        let widened = low.checked_add(1)
+            .and_then(|size| size.checked_add(high))
+            .filter(|&size| size <= isize::MAX as usize);
+
+        // without headroom, the rows still fit exactly
+        Ok(widened.map_or((needed_low, needed_size), |size| (low, size)))
+    }
+
+    /// The frame from [`frame_for`](Self::frame_for) if rows over `entries` in it fit the
+    /// memory budget, otherwise the one that just fits their sums, if rows in that do.
+    fn frame_within_budget(&self, entries: &[i64]) -> Result<(usize, usize), SolveError> {
+        let frame = self.frame_for(entries)?;
+        let Some(budget) = self.options.memory_budget else {
+            return Ok(frame);
+        };
+
+        if (entries.len() as u64).saturating_mul(memory::row_bytes(frame.1)) <= budget {
+            return Ok(frame);
+        }
+
+        let required = estimate_memory(entries, Strategy::Full);
+        if required > budget {
+            return Err(SolveError::MemoryBudgetExceeded { strategy: Strategy::Full, required, budget });
+        }
+
+        log::debug!("Widening the rows without headroom to fit the memory budget");
+        dimensions(entries)
+    }
+
+    fn solver(&self, target_index: usize, started: Instant) -> Solver<'_> {
+        Solver {
+            started,
+            target: self.target,
+            target_index,
+            ..Solver::for_rows(&self.entries, self.zero_index, self.sum_size, self.kernel)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Outcome;
+
+    fn from_scratch(target: i64, entries: &[i64]) -> Option<Solution> {
+        match crate::run_algorithm(target, entries.to_vec(), &Options::default(), None).unwrap() {
+            Outcome::Exact(solution) => solution,
+            Outcome::BestEffort(best) => panic!("no deadline was set, got {best:?}"),
+        }
+    }
+
+    fn assert_agrees(solver: &mut IncrementalSolver) {
+        let reachable = crate::reachable_sums(solver.entries(), &Options::default(), None).unwrap();
+        assert!(solver.reachable_sums().iter().eq(reachable.iter()), "{:?}", solver.entries());
+
+        for target in -60..=60 {
+            solver.set_target(target);
+
+            let incremental = solver.solve();
+            assert_eq!(incremental.is_some(), from_scratch(target, solver.entries()).is_some(), "{target} from {:?}", solver.entries());
+            if let Some(solution) = incremental {
+                assert!(solution.verified && solution.is_exact(), "{solution:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn edits_agree_with_solving_from_scratch() {
+        let mut solver = IncrementalSolver::new(0, &Options::default()).unwrap();
+        assert_agrees(&mut solver);
+
+        solver.extend([3, 5], None).unwrap();
+        assert_agrees(&mut solver);
+
+        // widens the frame below zero, then above it
+        solver.extend([-7, 0, 12], None).unwrap();
+        assert_agrees(&mut solver);
+        solver.extend([20, -1], None).unwrap();
+        assert_agrees(&mut solver);
+
+        assert_eq!(solver.remove(2, None).unwrap(), -7);
+        assert_agrees(&mut solver);
+
+        assert_eq!(solver.set_entries(vec![3, 5, 0, -9, 4], None).unwrap(), 3);
+        assert_agrees(&mut solver);
+        assert_eq!(solver.set_entries(vec![3, 5, 0, -9, 4], None).unwrap(), 5);
+
+        let budget = Options { memory_budget: Some(8 * memory::row_bytes(9)), ..Options::default() };
+        let mut tight = IncrementalSolver::new(0, &budget).unwrap();
+        tight.extend([1; 8], None).unwrap();
+        assert!(matches!(tight.extend([1], None), Err(SolveError::MemoryBudgetExceeded { .. })));
+        assert_eq!(tight.entries().len(), 8);
+    }
+
+    #[test]
+    fn widening_drops_the_headroom_that_would_go_over_the_budget() {
+        // the headroom would widen 0..=600 to 0..=1200 sums, but 0..=700 is all that fits
+        let budget = Options { memory_budget: Some(estimate_memory(&[600, 100], Strategy::Full)), ..Options::default() };
+        let mut solver = IncrementalSolver::new(0, &budget).unwrap();
+        solver.extend([600], None).unwrap();
+        solver.extend([100], None).unwrap();
+        assert_eq!(solver.sum_size, 701);
+        assert_agrees(&mut solver);
+
+        assert!(matches!(solver.extend([1], None), Err(SolveError::MemoryBudgetExceeded { .. })));
+        assert_eq!(solver.entries(), [600, 100]);
+    }
+}
@@ -0,0 +1,423 @@
+//! Word-at-a-time kernels for filling DP rows.
+//!
+//! A row is a plain `[u64]`: bit `j` of the row is bit `j % 64` of word `j / 64`.
+//! Completed rows are only ever read, so nothing here needs atomics; the row being
+//! written is split into disjoint chunks, one per rayon task.
+
+use std::{fmt, simd::{num::SimdUint, u64x8}, str::FromStr};
+
+use rayon::{prelude::ParallelSliceMut, iter::{IndexedParallelIterator, ParallelIterator}};
+
+/// Words handed to each rayon task: big enough to amortise scheduling, small enough
+/// to keep every thread busy on short rows.
+const CHUNK_WORDS: usize = 4096;
+
+const LANES: usize = 8;
+
+/// The number of words needed to hold `bits` bits.
+pub const fn words_for(bits: usize) -> usize {
+    bits.div_ceil(64)
+}
+
+/// Returns bit `index` of `row`.
+pub fn get(row: &[u64], index: usize) -> bool {
+    row[index / 64] >> (index % 64) & 1 != 0
+}
+
+/// Sets bit `index` of `row`.
+pub fn set(row: &mut [u64], index: usize) {
+    row[index / 64] |= 1 << (index % 64);
+}
+
+/// The set bit of `row` closest to `index`, preferring the lower one on a tie.
+/// `index` may lie past the end of the row.
+pub fn nearest_one(row: &[u64], index: usize) -> Option<usize> {
+    match (prev_one(row, index), next_one(row, index)) {
+        (Some(below), Some(above)) if above - index < index - below => Some(above),
+        (Some(below), _) => Some(below),
+        (None, above) => above,
+    }
+}
+
+/// A copy of `row`, `words` words long, with every bit moved up by `shift` (down if
+/// negative). Bits moved past either end are dropped.
+pub fn resized(row: &[u64], words: usize, shift: isize) -> Vec<u64> {
+    (0..words).map(|w| shifted_word(row, w, shift)).collect()
+}
+
+/// The lowest set bit of `row` at or above `index`. Like the vendored
+/// `AtomicBitVec::next_one_from`, for plain words; the tests check both.
+pub fn next_one(row: &[u64], index: usize) -> Option<usize> {
+    let mut w = index / 64;
+    let mut word = *row.get(w)? & (u64::MAX << (index % 64));
+
+    loop {
+        if word != 0 {
+            return Some(w * 64 + word.trailing_zeros() as usize);
+        }
+
+        w += 1;
+        word = *row.get(w)?;
+    }
+}
+
+/// The lowest clear bit of `row` at or above `index`, or `None` if every bit from
+/// `index` to the end of the row is set.
+pub fn next_zero(row: &[u64], index: usize) -> Option<usize> {
+    let mut w = index / 64;
+    let mut word = !*row.get(w)? & (u64::MAX << (index % 64));
+
+    loop {
+        if word != 0 {
+            return Some(w * 64 + word.trailing_zeros() as usize);
+        }
+
+        w += 1;
+        word = !*row.get(w)?;
+    }
+}
+
+/// The highest set bit of `row` at or below `index`. Like the vendored
+/// `AtomicBitVec::prev_one_from`, for plain words; the tests check both.
+fn prev_one(row: &[u64], index: usize) -> Option<usize> {
+    let index = index.min((row.len() * 64).checked_sub(1)?);
+    let mut w = index / 64;
+    let mut word = row[w] & (u64::MAX >> (63 - index % 64));
+
+    loop {
+        if word != 0 {
+            return Some(w * 64 + 63 - word.leading_zeros() as usize);
+        }
+
+        w = w.checked_sub(1)?;
+        word = row[w];
+    }
+}
+
+/// Which implementation of the row operations to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Kernel {
+    /// One word at a time. Available everywhere.
+    Scalar,
+    /// Portable `std::simd`, eight words at a time, using whatever vector
+    /// instructions the binary was compiled for.
+    Simd,
+    /// The [`Kernel::Simd`] code compiled for AVX2 and `popcnt`, picked at runtime
+    /// on x86-64 CPUs that support them.
+    Avx2,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 3] = [Kernel::Scalar, Kernel::Simd, Kernel::Avx2];
+
+    /// The fastest kernel this CPU supports.
+    pub fn detect() -> Kernel {
+        if Kernel::Avx2.is_available() {
+            Kernel::Avx2
+        } else {
+            Kernel::Simd
+        }
+    }
+
+    /// Whether this kernel can run on this CPU.
+    pub fn is_available(self) -> bool {
+        match self {
+            Kernel::Scalar | Kernel::Simd => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => is_x86_feature_detected!("avx2") && is_x86_feature_detected!("popcnt"),
+            #[cfg(not(target_arch = "x86_64"))]
+            Kernel::Avx2 => false,
+        }
+    }
+
+    /// Overwrites `row` with `previous | (previous << shift)`, i.e. every sum in `previous`
+    /// plus every sum in `previous` moved up by `shift` (down if negative). Bits shifted
+    /// past either end of the row are dropped.
+    ///
+    /// # Panics
+    /// Panics if `previous` and `row` differ in length, or if this kernel is not
+    /// [available](Kernel::is_available).
+    pub fn shift_or(self, previous: &[u64], row: &mut [u64], shift: isize) {
+        assert_eq!(previous.len(), row.len(), "rows must be the same length");
+        assert!(self.is_available(), "the {self} kernel is not supported on this CPU");
+
+        row.par_chunks_mut(CHUNK_WORDS)
+            .enumerate()
+            .for_each(|(chunk, words)| {
+                let start = chunk * CHUNK_WORDS;
+                match self {
+                    Kernel::Scalar => shift_or_scalar(previous, words, start, shift),
+                    Kernel::Simd => shift_or_simd(previous, words, start, shift),
+                    #[cfg(target_arch = "x86_64")]
+                    // SAFETY: `is_available` checked that the CPU supports AVX2 and popcnt
+                    Kernel::Avx2 => unsafe { shift_or_avx2(previous, words, start, shift) },
+                    #[cfg(not(target_arch = "x86_64"))]
+                    Kernel::Avx2 => unreachable!(),
+                }
+            });
+    }
+
+    /// Counts the set bits in `row`.
+    ///
+    /// # Panics
+    /// Panics if this kernel is not [available](Kernel::is_available).
+    pub fn count_ones(self, row: &[u64]) -> u64 {
+        assert!(self.is_available(), "the {self} kernel is not supported on this CPU");
+
+        match self {
+            Kernel::Scalar => row.iter().map(|word| word.count_ones() as u64).sum(),
+            Kernel::Simd => count_ones_simd(row),
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `is_available` checked that the CPU supports AVX2 and popcnt
+            Kernel::Avx2 => unsafe { count_ones_avx2(row) },
+            #[cfg(not(target_arch = "x86_64"))]
+            Kernel::Avx2 => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kernel::Scalar => write!(f, "scalar"),
+            Kernel::Simd => write!(f, "simd"),
+            Kernel::Avx2 => write!(f, "avx2"),
+        }
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Kernel::ALL.into_iter()
+            .find(|kernel| kernel.to_string() == s)
+            .ok_or_else(|| format!("unknown kernel `{s}` (expected one of: scalar, simd, avx2)"))
+    }
+}
+
+/// Fills `words`, which start at word `start` of the row, one word at a time.
+fn shift_or_scalar(previous: &[u64], words: &mut [u64], start: usize, shift: isize) {
+    for (offset, word) in words.iter_mut().enumerate() {
+        let w = start + offset;
+        *word = previous[w] | shifted_word(previous, w, shift);
+    }
+}
+
+/// Fills `words` like [`shift_or_scalar`], but [`LANES`] words at a time wherever every
+/// source word is in bounds. The words at the ends of the row fall back to scalar.
+#[inline(always)]
+fn shift_or_simd(previous: &[u64], words: &mut [u64], start: usize, shift: isize) {
+    let word_shift = shift.div_euclid(64);
+    let bits = shift.rem_euclid(64) as u64;
+
+    let len = previous.len() as isize;
+    // output words whose sources `w - word_shift - 1` and `w - word_shift` are both in the row
+    let first = (word_shift + 1).clamp(0, len) as usize;
+    let end = (len + word_shift).clamp(0, len) as usize;
+
+    let mut offset = 0;
+    while offset < words.len() {
+        let w = start + offset;
+
+        if w >= first && w + LANES <= end && offset + LANES <= words.len() {
+            let source = (w as isize - word_shift) as usize;
+
+            let current = u64x8::from_slice(&previous[w..]);
+            let upper = u64x8::from_slice(&previous[source..]);
+            let shifted = if bits == 0 {
+                upper
+            } else {
+                let lower = u64x8::from_slice(&previous[source - 1..]);
+                (upper << bits) | (lower >> (64 - bits))
+            };
+
+            (current | shifted).copy_to_slice(&mut words[offset..offset + LANES]);
+            offset += LANES;
+        } else {
+            words[offset] = previous[w] | shifted_word(previous, w, shift);
+            offset += 1;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,popcnt")]
+fn shift_or_avx2(previous: &[u64], words: &mut [u64], start: usize, shift: isize) {
+    shift_or_simd(previous, words, start, shift)
+}
+
+#[inline(always)]
+fn count_ones_simd(row: &[u64]) -> u64 {
+    let (head, body, tail) = row.as_simd::<LANES>();
+
+    let mut total = u64x8::splat(0);
+    for words in body {
+        total += words.count_ones();
+    }
+
+    total.reduce_sum()
+        + head.iter().chain(tail).map(|word| word.count_ones() as u64).sum::<u64>()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,popcnt")]
+fn count_ones_avx2(row: &[u64]) -> u64 {
+    count_ones_simd(row)
+}
+
+/// Word `w` of `row` as it would be after shifting the whole row by `shift` bits. Rows are
+/// plain words rather than an `AtomicBitVec`, so this mirrors the vendored `shifted_block`,
+/// and the tests check both against the same bit-by-bit reference.
+fn shifted_word(row: &[u64], w: usize, shift: isize) -> u64 {
+    let word_at = |i: isize| if i >= 0 && (i as usize) < row.len() { row[i as usize] } else { 0 };
+
+    let w = w as isize;
+    let words = shift.div_euclid(64);
+    let bits = shift.rem_euclid(64) as u32;
+
+    if bits == 0 {
+        word_at(w - words)
+    } else {
+        (word_at(w - words) << bits) | (word_at(w - words - 1) >> (64 - bits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use atomic_bitvec::{AtomicBitVec, BitVec};
+
+    use super::*;
+
+    fn sample_row(words: usize, mut state: u64) -> Vec<u64> {
+        (0..words)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            })
+            .collect()
+    }
+
+    fn atomic(row: &[u64]) -> AtomicBitVec {
+        BitVec::from_blocks(row.to_vec(), row.len() * 64).into_atomic()
+    }
+
+    /// Bit `i` of `row`, clear outside it: the reference both the helpers here and their
+    /// counterparts in atomic-bitvec are checked against.
+    fn bit(row: &[u64], i: isize) -> bool {
+        i >= 0 && (i as usize) < row.len() * 64 && get(row, i as usize)
+    }
+
+    #[test]
+    fn shifts_agree_with_atomic_bitvec_and_bit_by_bit() {
+        for (words, out_words) in [(1, 1), (3, 5), (9, 2), (17, 17)] {
+            let previous = sample_row(words, words as u64 + 3);
+            let bits = words as isize * 64;
+
+            for shift in [0, 1, 63, 64, 65, 130, bits - 1, bits, bits + 1] {
+                for shift in [shift, -shift] {
+                    let moved = resized(&previous, out_words, shift);
+                    let expected = (0..out_words * 64).map(|i| bit(&previous, i as isize - shift));
+                    assert!((0..out_words * 64).map(|i| get(&moved, i)).eq(expected), "{words} to {out_words} words, shift {shift}");
+
+                    let vendored = atomic(&vec![0; out_words]);
+                    vendored.or_shifted_from(&atomic(&previous), shift, Ordering::AcqRel);
+                    assert_eq!(vendored.into_frozen().as_blocks(), moved, "{words} to {out_words} words, shift {shift}");
+
+                    if words == out_words {
+                        let mut row = vec![0; words];
+                        Kernel::Scalar.shift_or(&previous, &mut row, shift);
+                        let vendored = atomic(&previous);
+                        vendored.or_shifted_from(&vendored, shift, Ordering::AcqRel);
+                        assert_eq!(vendored.into_frozen().as_blocks(), row, "{words} words, shift {shift}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_agree_with_scalar() {
+        for words in [1, 7, 8, 9, 100, CHUNK_WORDS + 37] {
+            let previous = sample_row(words, words as u64 + 1);
+            let bits = words as isize * 64;
+
+            for shift in [0, 1, 63, 64, 65, 127, 128, 500, bits - 1, bits, bits + 3] {
+                for shift in [shift, -shift] {
+                    let mut expected = vec![0; words];
+                    Kernel::Scalar.shift_or(&previous, &mut expected, shift);
+
+                    for kernel in Kernel::ALL.into_iter().filter(|kernel| kernel.is_available()) {
+                        let mut row = vec![u64::MAX; words];
+                        kernel.shift_or(&previous, &mut row, shift);
+                        assert_eq!(row, expected, "{kernel} kernel, {words} words, shift {shift}");
+                        assert_eq!(kernel.count_ones(&row), Kernel::Scalar.count_ones(&expected));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn searches_agree_with_atomic_bitvec_and_bit_by_bit() {
+        for words in [0, 1, 2, 9] {
+            // sparse, dense and random rows
+            let rows = [
+                (0..words).map(|w| 1 << (w * 7 % 64)).collect(),
+                (0..words).map(|w| !(1 << (w * 11 % 64))).collect(),
+                sample_row(words, words as u64 + 5),
+            ];
+
+            for row in rows {
+                let vendored = atomic(&row);
+                let bits = words * 64;
+                for index in (0..bits + 70).step_by(3) {
+                    let expected_next = (index..bits).find(|&i| bit(&row, i as isize));
+                    let expected_prev = (0..=index).rev().find(|&i| bit(&row, i as isize));
+                    assert_eq!(next_one(&row, index), expected_next, "{row:x?} {index}");
+                    assert_eq!(prev_one(&row, index), expected_prev, "{row:x?} {index}");
+                    assert_eq!(vendored.next_one_from(index, Ordering::Acquire), expected_next, "{row:x?} {index}");
+                    assert_eq!(vendored.prev_one_from(index, Ordering::Acquire), expected_prev, "{row:x?} {index}");
+                    assert_eq!(next_zero(&row, index), (index..bits).find(|&i| !bit(&row, i as isize)), "{row:x?} {index}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_one_prefers_closest_then_lower() {
+        let mut row = vec![0; 4];
+        assert_eq!(nearest_one(&row, 10), None);
+
+        set(&mut row, 10);
+        set(&mut row, 150);
+        assert_eq!(nearest_one(&row, 0), Some(10));
+        assert_eq!(nearest_one(&row, 10), Some(10));
+        assert_eq!(nearest_one(&row, 80), Some(10));
+        assert_eq!(nearest_one(&row, 81), Some(150));
+        assert_eq!(nearest_one(&row, 1000), Some(150));
+    }
+
+    #[test]
+    fn scalar_shift_moves_bits() {
+        let mut previous = vec![0; 3];
+        set(&mut previous, 70);
+
+        let mut row = vec![0; 3];
+        Kernel::Scalar.shift_or(&previous, &mut row, 60);
+        assert!(get(&row, 70) && get(&row, 130));
+        assert_eq!(Kernel::Scalar.count_ones(&row), 2);
+
+        Kernel::Scalar.shift_or(&previous, &mut row, -70);
+        assert!(get(&row, 70) && get(&row, 0));
+        assert_eq!(Kernel::Scalar.count_ones(&row), 2);
+
+        Kernel::Scalar.shift_or(&previous, &mut row, 200);
+        assert_eq!(Kernel::Scalar.count_ones(&row), 1);
+    }
+}
@@ -1,132 +1,754 @@
-use std::{ops::Neg, sync::atomic::{AtomicU32, Ordering, AtomicU64, AtomicUsize}};
+#![feature(portable_simd)]
 
-use atomic_bitvec::AtomicBitVec;
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::{fmt, path::{Path, PathBuf}, str::FromStr, sync::{Arc, atomic::{AtomicU32, Ordering}}, time::Instant};
 
-pub fn run_algorithm(target: i64, entries: Vec<i64>, progress: Option<&AtomicUsize>) -> Option<Vec<i64>> {
-    let total = entries.len();
+use rayon::{prelude::{IntoParallelIterator, ParallelIterator}, ThreadPool, ThreadPoolBuilder};
 
-    let most_negative: usize = entries.iter()
-        .copied()
-        .filter(|x| x.is_negative())
-        .map(|x| x.neg() as usize)
-        .sum();
+mod approx;
+mod bytes;
+mod checkpoint;
+mod compressed;
+mod heuristic;
+mod incremental;
+pub mod kernel;
+mod memory;
+mod observer;
+mod reachable;
+mod solution;
+mod storage;
 
-    let most_positive: usize = entries.iter()
-        .copied()
-        .filter(|x| x.is_positive())
-        .map(|x| x as usize)
-        .sum();
+use checkpoint::Checkpoint;
+use storage::Table;
+
+pub use approx::{approximate, estimate_approx_memory, ApproxOptions, Approximation};
+pub use bytes::Bytes;
+pub use checkpoint::{CheckpointHeader, CheckpointOptions};
+pub use kernel::Kernel;
+pub use incremental::IncrementalSolver;
+pub use memory::{choose_strategy, estimate_memory};
+pub use observer::Observer;
+pub use reachable::{reachable_sums, ReachableSums};
+pub use solution::{Solution, Source};
+pub use storage::Storage;
+
+/// How the DP rows are kept around for backtracking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Strategy {
+    /// Keep every row of the table. Fastest, but needs `entries × sum_size` bits.
+    Full,
+    /// Keep only every `√entries`-th row and recompute the rows in between while
+    /// backtracking. Needs roughly `2√entries` rows, at the cost of filling the
+    /// table about twice.
+    LowMemory,
+}
+
+impl Strategy {
+    /// Every strategy, from fastest to most frugal.
+    pub const ALL: [Strategy; 2] = [Strategy::Full, Strategy::LowMemory];
+
+    fn rows_held(self, total: usize) -> usize {
+        match self {
+            Strategy::Full => total,
+            Strategy::LowMemory => {
+                let stride = low_memory_stride(total);
+                total.div_ceil(stride) + stride + 1
+            }
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Full => write!(f, "full"),
+            Strategy::LowMemory => write!(f, "low-memory"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::ALL.into_iter()
+            .find(|strategy| strategy.to_string() == s)
+            .ok_or_else(|| format!("unknown strategy `{s}` (expected one of: full, low-memory)"))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Use this strategy instead of the fastest one that fits the memory budget.
+    pub strategy: Option<Strategy>,
+    /// The most bytes the DP rows may occupy. Unlimited if `None`.
+    pub memory_budget: Option<u64>,
+    /// Fill rows on a dedicated pool of this many threads instead of rayon's global pool.
+    /// Ignored if `thread_pool` is set.
+    pub threads: Option<usize>,
+    /// Fill rows on this pool instead of rayon's global pool, e.g. to share one pool
+    /// between several solves.
+    pub thread_pool: Option<Arc<ThreadPool>>,
+    /// The row kernel to use. Defaults to the fastest one this CPU supports.
+    pub kernel: Option<Kernel>,
+    /// Stop filling rows as soon as the target is reachable and backtrack from there,
+    /// instead of always filling the whole table.
+    pub early_exit: bool,
+    /// Give up on the exact search once it is clear it won't finish by this time, and
+    /// return the closest subset that could be found instead.
+    pub deadline: Option<Instant>,
+    /// Save the filled rows to a file as the solve goes, so it can be picked up again
+    /// with [`resume_algorithm`].
+    pub checkpoint: Option<CheckpointOptions>,
+    /// Where to keep the rows. Defaults to [`Storage::Memory`]. The memory budget isn't
+    /// checked with [`Storage::Mapped`], as the rows live in a file. With
+    /// [`Storage::Compressed`] their size depends on what they hold, so it is checked
+    /// after every row is stored instead, failing the solve once they outgrow it.
+    pub storage: Option<Storage>,
+    /// The directory mapped storage puts its temporary file in. Defaults to the system
+    /// temp directory, which may itself be held in memory.
+    pub storage_dir: Option<PathBuf>,
+}
+
+/// The result of a solve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    /// A definitive answer: entries adding up to exactly the target, or `None` if no
+    /// subset does. Entries of 0 are never part of the subset, as any of them could be
+    /// added to it without changing its sum.
+    Exact(Option<Solution>),
+    /// The deadline came before the search could give a definitive answer.
+    BestEffort(BestEffort),
+}
+
+/// The closest the search got to the target before its deadline. This is not
+/// exhaustive: a subset adding up to the target may still exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BestEffort {
+    /// Entries adding up to as close to the target as could be found in time.
+    pub closest: Solution,
+    /// How many rows the exact search filled: no subset of the first `rows_completed`
+    /// entries adds up to the target.
+    pub rows_completed: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    MemoryBudgetExceeded { strategy: Strategy, required: u64, budget: u64 },
+    ThreadPool(String),
+    KernelUnavailable(Kernel),
+    Checkpoint(String),
+    NoCheckpoint,
+    Storage(String),
+    /// The entries' sums span more values than this platform can index, so no table can
+    /// hold them. The approximate solver can still handle them.
+    SumsTooWide,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::MemoryBudgetExceeded { strategy, required, budget } => write!(
+                f,
+                "the {strategy} strategy needs {} but the memory budget is {}",
+                Bytes(*required),
+                Bytes(*budget),
+            ),
+            SolveError::ThreadPool(err) => write!(f, "could not start the thread pool: {err}"),
+            SolveError::KernelUnavailable(kernel) => write!(f, "the {kernel} kernel is not supported on this CPU"),
+            SolveError::Checkpoint(err) => write!(f, "could not use the checkpoint file {err}"),
+            SolveError::NoCheckpoint => write!(f, "no checkpoint file to resume from was given"),
+            SolveError::Storage(err) => write!(f, "could not map the DP table to a file: {err}"),
+            SolveError::SumsTooWide => write!(f, "the entries' sums span too many values for a DP table"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+pub fn run_algorithm(target: i64, entries: Vec<i64>, options: &Options, observer: Option<&dyn Observer>) -> Result<Outcome, SolveError> {
+    let strategy = choose_strategy(&entries, options)?;
+
+    let header = CheckpointHeader { target, entries, strategy };
+    let checkpoint = options.checkpoint.as_ref()
+        .map(|checkpoint| checkpoint::Writer::create(checkpoint, &header).map(Checkpoint::Created))
+        .transpose()?;
+
+    solve(header, options, observer, checkpoint)
+}
+
+/// Picks up the solve saved in the checkpoint file `options.checkpoint` where it left
+/// off, using the same target, entries and strategy, and carries on saving to that file.
+/// The saved rows are only read once the strategy is known to fit the memory budget, and
+/// go straight into the table the solve fills.
+pub fn resume_algorithm(options: &Options, observer: Option<&dyn Observer>) -> Result<Outcome, SolveError> {
+    let checkpoint = options.checkpoint.as_ref().ok_or(SolveError::NoCheckpoint)?;
+    let resume = checkpoint::Resume::open(checkpoint)?;
+
+    let forced = Options { strategy: Some(resume.header.strategy), ..options.clone() };
+    choose_strategy(&resume.header.entries, &forced)?;
+
+    let header = resume.header.clone();
+    solve(header, options, observer, Some(Checkpoint::Resumed(resume)))
+}
+
+/// Solves the problem in `header` with its strategy, starting from the rows saved in
+/// `checkpoint` if it is being resumed.
+fn solve(header: CheckpointHeader, options: &Options, observer: Option<&dyn Observer>, checkpoint: Option<Checkpoint>) -> Result<Outcome, SolveError> {
+    let CheckpointHeader { target, entries, strategy } = header;
+    log::info!("Using the {strategy} strategy ({})", Bytes(estimate_memory(&entries, strategy)));
+
+    let kernel = kernel_for(options)?;
+    log::info!("Using the {kernel} kernel");
+
+    let started = Instant::now();
+    let (zero_index, sum_size) = dimensions(&entries)?;
+    log::debug!("sum_size={}", sum_size);
+
+    let Some(target_index) = index_of(target, zero_index, sum_size) else {
+        log::info!("The target {target} is outside every possible sum");
+        return Ok(Outcome::Exact(None));
+    };
+
+    let solver = Solver {
+        entries: &entries,
+        strategy,
+        started,
+        zero_index,
+        sum_size,
+        words: kernel::words_for(sum_size),
+        target,
+        target_index,
+        kernel,
+        storage: options.storage.unwrap_or(Storage::Memory),
+        storage_dir: options.storage_dir.as_deref(),
+        memory_budget: options.memory_budget,
+        early_exit: options.early_exit,
+        deadline: options.deadline,
+        observer,
+    };
+
+    if entries.is_empty() {
+        // only the empty subset, adding up to 0, which is the only sum in range
+        return Ok(Outcome::Exact(Some(solver.solution(vec![], Source::Table))));
+    }
+
+    let outcome = with_pool(options, || match strategy {
+        Strategy::Full => solver.run_full(checkpoint),
+        Strategy::LowMemory => solver.run_low_memory(checkpoint),
+    })??;
+
+    match &outcome {
+        Outcome::Exact(solution) => {
+            log::info!("Does a total of {target} exist? {}", solution.is_some());
+        }
+        Outcome::BestEffort(best) => {
+            log::info!("Out of time after {} rows, closest sum found is {}", best.rows_completed, best.closest.sum);
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// The kernel `options` asks for, or the fastest one this CPU supports.
+fn kernel_for(options: &Options) -> Result<Kernel, SolveError> {
+    match options.kernel {
+        Some(kernel) if !kernel.is_available() => Err(SolveError::KernelUnavailable(kernel)),
+        Some(kernel) => Ok(kernel),
+        None => Ok(Kernel::detect()),
+    }
+}
+
+/// Runs `f` on the pool `options` asks for, or on the current (usually global) pool.
+fn with_pool<R: Send>(options: &Options, f: impl FnOnce() -> R + Send) -> Result<R, SolveError> {
+    match (&options.thread_pool, options.threads) {
+        (Some(pool), _) => Ok(pool.install(f)),
+        (None, Some(threads)) => {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|err| SolveError::ThreadPool(err.to_string()))?;
+
+            Ok(pool.install(f))
+        }
+        (None, None) => Ok(f()),
+    }
+}
+
+/// The index of the sum 0 in a row (the total magnitude of the negative entries), and
+/// the number of sums a row covers.
+///
+/// Fails unless a row fits in `isize::MAX` bits. Within that domain every entry, shift,
+/// sum and index in the table fits in an `isize`, so the solve itself can't overflow.
+fn dimensions(entries: &[i64]) -> Result<(usize, usize), SolveError> {
+    let mut most_negative: usize = 0;
+    let mut most_positive: usize = 0;
+
+    for &entry in entries {
+        let magnitude = usize::try_from(entry.unsigned_abs()).map_err(|_| SolveError::SumsTooWide)?;
+        let total = if entry < 0 { &mut most_negative } else { &mut most_positive };
+        *total = total.checked_add(magnitude).ok_or(SolveError::SumsTooWide)?;
+    }
 
     let zero_index = most_negative;
 
-    let sum_size = most_negative + 1 + most_positive;
-    println!("sum_size={}", sum_size);
+    let sum_size = most_negative.checked_add(1)
+        .and_then(|size| size.checked_add(most_positive))
+        .filter(|&size| size <= isize::MAX as usize)
+        .ok_or(SolveError::SumsTooWide)?;
+
+    Ok((zero_index, sum_size))
+}
+
+/// The index of `sum` in a row, or `None` if no subset can add up to it.
+fn index_of(sum: i64, zero_index: usize, sum_size: usize) -> Option<usize> {
+    isize::try_from(sum).ok()
+        .and_then(|sum| zero_index.checked_add_signed(sum))
+        .filter(|&index| index < sum_size)
+}
 
-    let dp_table = create_dp_table(sum_size, total);
+/// `entry` as a shift along a row. Lossless within the domain [`dimensions`] checks.
+fn shift(entry: i64) -> isize {
+    isize::try_from(entry).expect("entries are within isize")
+}
 
-    println!("Table successfully constructed");
+/// Everything a single solve needs to fill and walk the table.
+struct Solver<'a> {
+    entries: &'a [i64],
+    strategy: Strategy,
+    started: Instant,
+    zero_index: usize,
+    sum_size: usize,
+    words: usize,
+    target: i64,
+    target_index: usize,
+    kernel: Kernel,
+    storage: Storage,
+    storage_dir: Option<&'a Path>,
+    /// Only checked here for compressed storage, whose size isn't known up front. Other
+    /// storage was checked against it when the strategy was chosen.
+    memory_budget: Option<u64>,
+    early_exit: bool,
+    deadline: Option<Instant>,
+    observer: Option<&'a dyn Observer>,
+}
 
-    for i in 0..total {
-        if let Some(progress) = progress {
-            progress.store(i, Ordering::SeqCst)
+/// Why filling stopped before the last row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    FoundEarly,
+    OutOfTime,
+}
+
+impl<'a> Solver<'a> {
+    /// A solver for filling rows over `entries` with the full strategy, with the target
+    /// set to 0 and nothing to stop filling early.
+    fn for_rows(entries: &'a [i64], zero_index: usize, sum_size: usize, kernel: Kernel) -> Solver<'a> {
+        Solver {
+            entries,
+            strategy: Strategy::Full,
+            started: Instant::now(),
+            zero_index,
+            sum_size,
+            words: kernel::words_for(sum_size),
+            target: 0,
+            target_index: zero_index,
+            kernel,
+            storage: Storage::Memory,
+            storage_dir: None,
+            memory_budget: None,
+            early_exit: false,
+            deadline: None,
+            observer: None,
         }
-        println!("{}/{}", i, total);
-        
-        let current_entry = entries[i] as isize;
+    }
 
-        dp_table[i].set_true(zero_index);
-        
-        if i == 0 {
-            dp_table[i].set_true((zero_index as isize + current_entry) as usize);
-        } else {
-            (0..sum_size).into_par_iter()
-                .for_each(|j| {
-                    if dp_table[i - 1].load(j) {
-                        dp_table[i].set_true(j);
-                    }
-                });
-
-            (0..sum_size).into_par_iter()
-                .for_each(|j| {
-                    let index = (j as isize) - current_entry;
-                    if index < 0 {
-                        return;
-                    }
-
-                    if index as usize >= sum_size {
-                        return;
-                    }
-
-                    if dp_table[i - 1].load(index as usize) {
-                        dp_table[i].set_true(j);
-                    }
-                });
-        }
-    }
-
-    println!("Finished the table");
-    let exists = dp_table[total - 1].load((target as isize + zero_index as isize) as usize);
-    println!("Does a total of {target} exist? {exists}");
-
-    if exists {
-        let mut subset      = vec![];
-        let mut current_sum = (target as isize + zero_index as isize) as usize;
-
-        for current_i in (0..total).rev() {
-            if current_i == 0 || !dp_table[current_i - 1].load(current_sum) {
-                let must_include = entries[current_i];
-                println!("...must include {must_include} to make sum of {}", (current_sum as isize - zero_index as isize));
-
-                subset.push(must_include);
-                current_sum = ((current_sum as isize) - (must_include as isize)) as usize;
-                println!("   ...so now looking for sum of {}", (current_sum as isize - zero_index as isize));
+    /// Fills the whole table, carrying on after the rows restored from `checkpoint`.
+    fn run_full(&self, checkpoint: Option<Checkpoint>) -> Result<Outcome, SolveError> {
+        let total = self.entries.len();
+
+        let mut dp_table = Table::new(self.storage, self.words, total, self.storage_dir)?;
+        let (start, mut checkpoint) = checkpoint::restore(checkpoint, |i, row| dp_table.set_row(i, row))?;
+        if start > 0 {
+            log::info!("Resuming from {start} saved rows");
+        }
+        self.check_budget(&dp_table, FULL_WHOLE_ROWS)?;
+
+        log::debug!("Table successfully constructed");
+
+        let filling_since = Instant::now();
+        let mut last_row = total - 1;
+        let mut stop = None;
+
+        for (i, &entry) in self.entries.iter().enumerate().skip(start) {
+            self.report_progress(i);
+
+            dp_table.fill_row(i, |previous, row| self.fill_row(row, previous, entry));
+            self.check_budget(&dp_table, FULL_WHOLE_ROWS)?;
+
+            if let Some(checkpoint) = &mut checkpoint {
+                checkpoint.maybe_save(i + 1, |row| dp_table.row(row), i + 1);
             }
 
-            if current_sum == zero_index { break; }
+            stop = self.stop_after(i, &dp_table.row(i), start, filling_since);
+            if stop.is_some() {
+                last_row = i;
+                break;
+            }
         }
 
-        let sum: i64 = subset.iter().sum();
+        let last = dp_table.row(last_row);
+        log::info!("Finished the table ({} reachable sums)", self.kernel.count_ones(&last));
+        report_compression(&dp_table, last_row + 1, self.words);
 
-        println!("Sanity check: current_sum ({current_sum}) == zero_index ({zero_index})? {}", current_sum == zero_index);
-        println!("Sanity check: subset sum ({sum}) == target ({target})? {}", sum == target);
+        let out_of_time = stop == Some(Stop::OutOfTime);
+        let indices = self.backtrack_target(&last, out_of_time)
+            .map(|sum_index| self.backtrack(last_row, sum_index, |row, sum| dp_table.get(row, sum)));
 
-        println!("Subset: {:?}", subset);
+        Ok(self.outcome(indices, last_row + 1, out_of_time))
+    }
+
+    /// Solves with only every `stride`-th row kept (plus the last), where `stride` is
+    /// about `√total`. While backtracking, the rows of one stride-sized block at a time
+    /// are recomputed from the kept row just before it. Carries on after the kept rows
+    /// restored from `checkpoint`.
+    fn run_low_memory(&self, checkpoint: Option<Checkpoint>) -> Result<Outcome, SolveError> {
+        let total = self.entries.len();
+        let stride = low_memory_stride(total);
+
+        // kept row b is row `(b + 1) * stride - 1`
+        let mut kept = Table::new(self.storage, self.words, total / stride, self.storage_dir)?;
+        let (mut kept_rows, mut checkpoint) = checkpoint::restore(checkpoint, |b, row| kept.set_row(b, row))?;
+        if kept_rows > 0 {
+            log::info!("Resuming from {kept_rows} saved rows");
+        }
+        let start = kept_rows * stride;
+        let mut previous = match kept_rows {
+            0 => vec![0; self.words],
+            _ => kept.row(kept_rows - 1).into_owned(),
+        };
+        self.check_budget(&kept, stride + 1)?;
+        let mut row = vec![0; self.words];
+        let filling_since = Instant::now();
+        let mut last_row = total - 1;
+        let mut stop = None;
+
+        for (i, &entry) in self.entries.iter().enumerate().skip(start) {
+            self.report_progress(i);
+
+            self.fill_row(&mut row, (i > 0).then_some(&previous[..]), entry);
+            std::mem::swap(&mut previous, &mut row);
+
+            if (i + 1) % stride == 0 {
+                kept.set_row(kept_rows, &previous);
+                kept_rows += 1;
+                self.check_budget(&kept, stride + 1)?;
+
+                if let Some(checkpoint) = &mut checkpoint {
+                    checkpoint.maybe_save(kept_rows, |b| kept.row(b), i + 1);
+                }
+            }
+
+            stop = self.stop_after(i, &previous, start, filling_since);
+            if stop.is_some() {
+                last_row = i;
+                break;
+            }
+        }
+
+        // `previous` now holds the last row that was filled
+        log::info!("Finished the table ({} reachable sums)", self.kernel.count_ones(&previous));
+        report_compression(&kept, kept_rows, self.words);
+
+        let out_of_time = stop == Some(Stop::OutOfTime);
+        let Some(sum_index) = self.backtrack_target(&previous, out_of_time) else {
+            return Ok(self.outcome(None, last_row + 1, out_of_time));
+        };
+        drop((previous, row));
+
+        let mut block: Vec<Vec<u64>> = Vec::with_capacity(stride);
+        let mut block_index = None;
+
+        let indices = self.backtrack(last_row, sum_index, |row, sum| {
+            let b = row / stride;
+
+            if block_index != Some(b) {
+                block.clear();
+
+                let start = b * stride;
+                let end = usize::min(start + stride, last_row + 1);
+                let kept_before = b.checked_sub(1).map(|before| kept.row(before));
+                for (offset, &entry) in self.entries[start..end].iter().enumerate() {
+                    let mut next = vec![0; self.words];
+                    let previous = match offset {
+                        0 => kept_before.as_deref(),
+                        _ => block.last().map(Vec::as_slice),
+                    };
+                    self.fill_row(&mut next, previous, entry);
+                    block.push(next);
+                }
+
+                block_index = Some(b);
+            }
+
+            kernel::get(&block[row - b * stride], sum)
+        });
+
+        Ok(self.outcome(Some(indices), last_row + 1, out_of_time))
+    }
+
+    /// Fails if the compressed rows of `table`, along with the `whole_rows` rows the
+    /// strategy holds uncompressed at most, take up more than the memory budget. Does
+    /// nothing for uncompressed tables.
+    fn check_budget(&self, table: &Table, whole_rows: usize) -> Result<(), SolveError> {
+        let (Some(budget), Some(compressed)) = (self.memory_budget, table.compressed_bytes()) else {
+            return Ok(());
+        };
+
+        let required = compressed.saturating_add((whole_rows as u64).saturating_mul(self.words as u64 * 8));
+        if required > budget {
+            return Err(SolveError::MemoryBudgetExceeded { strategy: self.strategy, required, budget });
+        }
+
+        Ok(())
+    }
+
+    fn report_progress(&self, i: usize) {
+        if let Some(observer) = self.observer {
+            observer.row_started(i, self.entries.len());
+        }
+        log::trace!("{}/{}", i, self.entries.len());
+    }
+
+    /// Whether to stop filling after row `i`: in early exit mode once the target is
+    /// reachable in `row`, or once the remaining rows, at the rate rows from `first_row`
+    /// on have been filled since `filling_since`, would run past the deadline.
+    ///
+    /// With [`Strategy::LowMemory`], backtracking refills up to every row again, so the
+    /// projection counts the table's rows a second time. Stopping at any row then still
+    /// leaves time to refill the rows filled so far.
+    fn stop_after(&self, i: usize, row: &[u64], first_row: usize, filling_since: Instant) -> Option<Stop> {
+        let total = self.entries.len();
+        let remaining_rows = total - i - 1;
+        if remaining_rows == 0 {
+            return None;
+        }
+
+        if self.early_exit && kernel::get(row, self.target_index) {
+            log::info!("Found early at row {i}, skipping the remaining {remaining_rows} rows");
+            if let Some(observer) = self.observer {
+                observer.found_early(i, total);
+            }
+
+            return Some(Stop::FoundEarly);
+        }
+
+        if let Some(deadline) = self.deadline {
+            let refill_rows = match self.strategy {
+                Strategy::Full => 0,
+                Strategy::LowMemory => total,
+            };
+            let per_row = filling_since.elapsed().div_f64((i + 1 - first_row) as f64);
+            let remaining = per_row.mul_f64((remaining_rows + refill_rows) as f64);
+
+            if Instant::now().checked_add(remaining).is_none_or(|finish| finish > deadline) {
+                log::info!("Out of time at row {i}: the remaining {remaining_rows} rows and {refill_rows} refilled while backtracking would take about {remaining:?}");
+                return Some(Stop::OutOfTime);
+            }
+        }
 
-        Some(subset)
-    } else {
         None
     }
+
+    /// The sum index to backtrack to from the last row filled: the target if it is
+    /// reachable, otherwise the closest reachable sum if out of time.
+    fn backtrack_target(&self, last_row: &[u64], out_of_time: bool) -> Option<usize> {
+        if kernel::get(last_row, self.target_index) {
+            Some(self.target_index)
+        } else if out_of_time {
+            kernel::nearest_one(last_row, self.target_index)
+        } else {
+            None
+        }
+    }
+
+    /// Turns the indices backtracked from the table into an [`Outcome`]. When out of
+    /// time without having hit the target, the remaining time goes to a heuristic
+    /// search over every entry, and the closer of the two subsets wins.
+    fn outcome(&self, indices: Option<Vec<usize>>, rows_completed: usize, out_of_time: bool) -> Outcome {
+        let (indices, deadline) = match (indices, self.deadline) {
+            (indices, Some(deadline)) if out_of_time => (indices.unwrap_or_default(), deadline),
+            (indices, _) => return Outcome::Exact(indices.map(|indices| self.solution(indices, Source::Table))),
+        };
+
+        let from_table = self.solution(indices, Source::Table);
+        if from_table.is_exact() {
+            return Outcome::Exact(Some(from_table));
+        }
+
+        let found = heuristic::closest_subset(self.entries, self.target, deadline)
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, chosen)| chosen)
+            .map(|(i, _)| i)
+            .collect();
+        let found = self.solution(found, Source::Heuristic);
+        log::info!("Heuristic search found a sum of {}, the partial table {}", found.sum, from_table.sum);
+
+        if found.is_exact() {
+            return Outcome::Exact(Some(found));
+        }
+
+        let closest = if found.sum.abs_diff(self.target) < from_table.sum.abs_diff(self.target) {
+            found
+        } else {
+            from_table
+        };
+
+        Outcome::BestEffort(BestEffort { closest, rows_completed })
+    }
+
+    /// A verified [`Solution`] picking the entries at `indices`, in any order, found
+    /// from `source`.
+    fn solution(&self, mut indices: Vec<usize>, source: Source) -> Solution {
+        indices.sort_unstable();
+        let amounts: Vec<i64> = indices.iter().map(|&i| self.entries[i]).collect();
+
+        let mut solution = Solution {
+            sum: amounts.iter().sum(),
+            indices,
+            amounts,
+            target: self.target,
+            source,
+            strategy: self.strategy,
+            kernel: self.kernel,
+            elapsed: self.started.elapsed(),
+            rows: self.entries.len(),
+            sum_size: self.sum_size,
+            verified: false,
+        };
+        solution.verified = solution.verify(self.entries);
+
+        solution
+    }
+
+    /// Fills `dp_row` with every sum reachable using `entry` and the sums in `previous`,
+    /// or just `0` and `entry` when this is the first row (in which case `dp_row` must
+    /// start out cleared).
+    fn fill_row(&self, dp_row: &mut [u64], previous: Option<&[u64]>, entry: i64) {
+        match previous {
+            None => {
+                kernel::set(dp_row, self.zero_index);
+                kernel::set(dp_row, index_of(entry, self.zero_index, self.sum_size).expect("entries are within the row"));
+            }
+            Some(previous) => self.kernel.shift_or(previous, dp_row, shift(entry)),
+        }
+    }
+
+    /// Walks back up the table from `last_row`, picking the indices of the entries that
+    /// make up the sum at `sum_index`. `reachable(i, j)` reports whether sum index `j` is
+    /// set in row `i`.
+    fn backtrack(&self, last_row: usize, sum_index: usize, mut reachable: impl FnMut(usize, usize) -> bool) -> Vec<usize> {
+        let zero_index = self.zero_index;
+
+        let mut indices     = vec![];
+        let mut current_sum = sum_index;
+
+        let sum_at = |index: usize| index as i128 - zero_index as i128;
+
+        for current_i in (0..=last_row).rev() {
+            if current_sum == zero_index { break; }
+
+            if current_i == 0 || !reachable(current_i - 1, current_sum) {
+                let must_include = self.entries[current_i];
+                log::trace!("...must include {must_include} to make sum of {}", sum_at(current_sum));
+
+                indices.push(current_i);
+                current_sum = current_sum.checked_add_signed(-shift(must_include)).expect("backtracked sums stay within the row");
+                log::trace!("   ...so now looking for sum of {}", sum_at(current_sum));
+            }
+        }
+
+        indices
+    }
+}
+
+/// Logs how much the first `rows` rows of `table` shrank, if it compresses them.
+fn report_compression(table: &Table, rows: usize, words: usize) {
+    if let Some(compressed) = table.compressed_bytes() {
+        let whole = (rows as u64).saturating_mul(words as u64 * 8);
+        log::info!("Compressed {rows} rows from {} to {}", Bytes(whole), Bytes(compressed));
+    }
 }
 
-fn create_dp_table(sum_size: usize, total: usize) -> Vec<AtomicBitVec> {
+/// The rows a compressed full table holds uncompressed: the last one filled, and the one
+/// being filled.
+const FULL_WHOLE_ROWS: usize = 2;
+
+fn low_memory_stride(total: usize) -> usize {
+    (total as f64).sqrt().ceil().max(1.0) as usize
+}
+
+fn create_dp_table(words: usize, total: usize) -> Vec<Vec<u64>> {
     let dp_table_progress = AtomicU32::new(0);
     (0..total).into_par_iter()
-        .map(|_| {
-            let mut bitvec = AtomicBitVec::with_bit_capacity(sum_size);
-            bitvec.resize_bits_with(sum_size, || AtomicU64::new(0));
-            bitvec
-        })
-        .inspect(|_| println!("{}/{total}", dp_table_progress.fetch_add(1, Ordering::SeqCst) + 1))
+        .map(|_| vec![0; words])
+        .inspect(|_| log::trace!("{}/{total}", dp_table_progress.fetch_add(1, Ordering::Relaxed) + 1))
         .collect::<Vec<_>>()
 }
 
-trait AtomicBitVecExt {
-    fn load(&self, index: usize) -> bool;
-    fn set_true(&self, index: usize);
-    fn set_false(&self, index: usize);
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl AtomicBitVecExt for AtomicBitVec {
-    fn load(&self, index: usize) -> bool {
-        self.get(index, Ordering::SeqCst)
+    /// A solver for `target` over `entries` whose deadline has already passed.
+    fn out_of_time(entries: &[i64], target: i64) -> Solver<'_> {
+        let (zero_index, sum_size) = dimensions(entries).unwrap();
+        let mut solver = Solver::for_rows(entries, zero_index, sum_size, Kernel::Scalar);
+        solver.target = target;
+        solver.deadline = Some(Instant::now());
+        solver
     }
 
-    fn set_true(&self, index: usize) {
-        self.set(index, true, Ordering::SeqCst);
+    #[test]
+    fn the_closer_of_the_table_and_the_heuristic_wins() {
+        // the greedy pick takes 7 and stops there, 2 away from the target
+        let entries = [7, 4, 4];
+        let solver = out_of_time(&entries, 9);
+
+        let closest = |outcome| match outcome {
+            Outcome::BestEffort(best) => (best.closest.sum, best.closest.source),
+            outcome => panic!("expected a best effort, got {outcome:?}"),
+        };
+
+        assert_eq!(closest(solver.outcome(Some(vec![1, 2]), 2, true)), (8, Source::Table));
+        assert_eq!(closest(solver.outcome(Some(vec![]), 1, true)), (7, Source::Heuristic));
+        assert_eq!(closest(solver.outcome(None, 1, true)), (7, Source::Heuristic));
     }
 
-    fn set_false(&self, index: usize) {
-        self.set(index, false, Ordering::SeqCst);
+    #[test]
+    fn compressed_tables_are_held_to_the_budget() {
+        let entries: Vec<i64> = (1..=200).map(|i| i * 7919 % 1000).collect();
+        let compressed = |strategy, budget| Options {
+            strategy: Some(strategy),
+            storage: Some(Storage::Compressed),
+            memory_budget: Some(budget),
+            ..Options::default()
+        };
+
+        for strategy in Strategy::ALL {
+            match run_algorithm(1, entries.clone(), &compressed(strategy, 64 << 10), None) {
+                Err(SolveError::MemoryBudgetExceeded { strategy: over, required, budget }) => {
+                    assert_eq!(over, strategy);
+                    assert!(required > budget, "{required} > {budget}");
+                }
+                other => panic!("{strategy}: expected to run over the budget, got {other:?}"),
+            }
+
+            let budget = estimate_memory(&entries, strategy);
+            assert!(run_algorithm(1, entries.clone(), &compressed(strategy, budget), None).is_ok(), "{strategy}");
+        }
     }
 }
@@ -0,0 +1,101 @@
+use crate::{dimensions, Options, SolveError, Storage, Strategy};
+
+/// The bytes `strategy` would need to hold its DP rows for `entries`.
+///
+/// This is the peak number of rows kept alive at once multiplied by the
+/// size of a single row, so it is what the solve will allocate up front
+/// (or at worst while backtracking), not including the entries themselves.
+/// Entries whose sums are [too wide](SolveError::SumsTooWide) for any table need `u64::MAX`.
+pub fn estimate_memory(entries: &[i64], strategy: Strategy) -> u64 {
+    let Ok((_, sum_size)) = dimensions(entries) else {
+        return u64::MAX;
+    };
+
+    (strategy.rows_held(entries.len()) as u64).saturating_mul(row_bytes(sum_size))
+}
+
+/// Picks the strategy a solve over `entries` will use under `options`.
+///
+/// An explicitly requested strategy is only checked against the budget. Otherwise
+/// the fastest strategy that fits is chosen, falling back to [`Strategy::LowMemory`]
+/// when the full table would not. Rows in [mapped storage](Storage::Mapped) don't
+/// count against the budget, and how small [compressed rows](Storage::Compressed) get
+/// isn't known until they are filled, so both always get the fastest strategy. The solve
+/// checks compressed rows against the budget as it fills them. Fails if no table can hold
+/// the entries' sums at all.
+pub fn choose_strategy(entries: &[i64], options: &Options) -> Result<Strategy, SolveError> {
+    dimensions(entries)?;
+
+    let candidates: &[Strategy] = match &options.strategy {
+        Some(strategy) => std::slice::from_ref(strategy),
+        None => &Strategy::ALL,
+    };
+
+    let budget = match (options.memory_budget, options.storage) {
+        (Some(budget), None | Some(Storage::Memory)) => budget,
+        _ => return Ok(candidates[0]),
+    };
+
+    for &strategy in candidates {
+        if estimate_memory(entries, strategy) <= budget {
+            return Ok(strategy);
+        }
+    }
+
+    let (strategy, required) = candidates.iter()
+        .map(|&strategy| (strategy, estimate_memory(entries, strategy)))
+        .min_by_key(|&(_, required)| required)
+        .unwrap();
+
+    Err(SolveError::MemoryBudgetExceeded { strategy, required, budget })
+}
+
+pub(crate) fn row_bytes(sum_size: usize) -> u64 {
+    let words = crate::kernel::words_for(sum_size) as u64;
+    std::mem::size_of::<Vec<u64>>() as u64 + words.saturating_mul(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_budget(budget: u64) -> Options {
+        Options { memory_budget: Some(budget), ..Options::default() }
+    }
+
+    #[test]
+    fn estimates_count_the_rows_each_strategy_holds() {
+        // sums 0..=100_000, and a low-memory stride of 10
+        let entries = vec![1000; 100];
+        assert_eq!(estimate_memory(&entries, Strategy::Full), 100 * row_bytes(100_001));
+        assert_eq!(estimate_memory(&entries, Strategy::LowMemory), (10 + 10 + 1) * row_bytes(100_001));
+        assert_eq!(estimate_memory(&[i64::MAX, i64::MAX], Strategy::Full), u64::MAX);
+    }
+
+    #[test]
+    fn strategies_fit_up_to_the_byte() {
+        let entries = vec![1000; 100];
+        let full = estimate_memory(&entries, Strategy::Full);
+        let low = estimate_memory(&entries, Strategy::LowMemory);
+
+        assert_eq!(choose_strategy(&entries, &Options::default()), Ok(Strategy::Full));
+        assert_eq!(choose_strategy(&entries, &with_budget(full)), Ok(Strategy::Full));
+        assert_eq!(choose_strategy(&entries, &with_budget(full - 1)), Ok(Strategy::LowMemory));
+        assert_eq!(choose_strategy(&entries, &with_budget(low)), Ok(Strategy::LowMemory));
+        assert_eq!(
+            choose_strategy(&entries, &with_budget(low - 1)),
+            Err(SolveError::MemoryBudgetExceeded { strategy: Strategy::LowMemory, required: low, budget: low - 1 }),
+        );
+
+        let forced = Options { strategy: Some(Strategy::Full), ..with_budget(full - 1) };
+        assert_eq!(
+            choose_strategy(&entries, &forced),
+            Err(SolveError::MemoryBudgetExceeded { strategy: Strategy::Full, required: full, budget: full - 1 }),
+        );
+
+        let mapped = Options { storage: Some(Storage::Mapped), ..with_budget(1) };
+        assert_eq!(choose_strategy(&entries, &mapped), Ok(Strategy::Full));
+
+        assert_eq!(choose_strategy(&[i64::MAX, i64::MAX], &Options::default()), Err(SolveError::SumsTooWide));
+    }
+}
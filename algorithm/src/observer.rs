@@ -0,0 +1,18 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Receives updates from a running solve. Every method does nothing by default.
+pub trait Observer: Sync {
+    /// Row `row` of `total` is about to be filled.
+    fn row_started(&self, _row: usize, _total: usize) {}
+
+    /// The target was already reachable after filling row `row`, so the rows after it
+    /// were skipped. Only happens with [`Options::early_exit`](crate::Options::early_exit).
+    fn found_early(&self, _row: usize, _total: usize) {}
+}
+
+/// Tracks the row currently being filled, e.g. for polling from another thread.
+impl Observer for AtomicUsize {
+    fn row_started(&self, row: usize, _total: usize) {
+        self.store(row, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,132 @@
+//! Every sum a set of entries can reach, read off the last row of the table.
+
+use std::ops::RangeInclusive;
+
+use crate::{dimensions, kernel, kernel_for, with_pool, Observer, Options, SolveError, Solver};
+
+/// The sums some subset of a set of entries adds up to. The empty subset always counts,
+/// so 0 is always reachable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReachableSums {
+    row: Vec<u64>,
+    zero_index: usize,
+    sum_size: usize,
+}
+
+/// Fills the table for `entries`, keeping only the last row. Needs two rows of memory
+/// whatever the strategy; of `options`, only the kernel and the thread pool apply.
+pub fn reachable_sums(entries: &[i64], options: &Options, observer: Option<&dyn Observer>) -> Result<ReachableSums, SolveError> {
+    let kernel = kernel_for(options)?;
+    let (zero_index, sum_size) = dimensions(entries)?;
+    let solver = Solver { observer, ..Solver::for_rows(entries, zero_index, sum_size, kernel) };
+
+    let row = with_pool(options, || {
+        // before any entry, only the empty subset
+        let mut row = vec![0; solver.words];
+        kernel::set(&mut row, zero_index);
+
+        let mut next = vec![0; solver.words];
+        for (i, &entry) in entries.iter().enumerate() {
+            solver.report_progress(i);
+            solver.fill_row(&mut next, Some(&row), entry);
+            std::mem::swap(&mut row, &mut next);
+        }
+
+        row
+    })?;
+
+    Ok(ReachableSums::new(row, zero_index, sum_size))
+}
+
+impl ReachableSums {
+    pub(crate) fn new(row: Vec<u64>, zero_index: usize, sum_size: usize) -> ReachableSums {
+        ReachableSums { row, zero_index, sum_size }
+    }
+
+    /// Whether some subset adds up to `sum`.
+    pub fn is_reachable(&self, sum: i64) -> bool {
+        self.index_of(sum).is_some_and(|index| kernel::get(&self.row, index))
+    }
+
+    /// The reachable sum closest to `sum`, preferring the lower one on a tie.
+    pub fn nearest_reachable(&self, sum: i64) -> i64 {
+        // every reachable sum is in the row, so the closest index in the row works as well
+        let index = (self.zero_index as i128 + sum as i128).clamp(0, self.sum_size as i128 - 1) as usize;
+        let nearest = kernel::nearest_one(&self.row, index).expect("0 is always reachable");
+
+        self.sum_at(nearest)
+    }
+
+    /// How many distinct sums are reachable.
+    pub fn count(&self) -> u64 {
+        self.row.iter().map(|word| word.count_ones() as u64).sum()
+    }
+
+    /// Every reachable sum, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        std::iter::successors(kernel::next_one(&self.row, 0), |&index| kernel::next_one(&self.row, index + 1))
+            .map(|index| self.sum_at(index))
+    }
+
+    /// The reachable sums as runs of consecutive sums, in increasing order. Much shorter
+    /// than [`iter`](ReachableSums::iter) when most sums in a stretch are reachable.
+    pub fn ranges(&self) -> impl Iterator<Item = RangeInclusive<i64>> + '_ {
+        let mut from = 0;
+        std::iter::from_fn(move || {
+            let start = kernel::next_one(&self.row, from)?;
+            // the row's padding bits are never set, so a run always ends within the row
+            let end = kernel::next_zero(&self.row, start).unwrap_or(self.sum_size);
+            from = end;
+
+            Some(self.sum_at(start)..=self.sum_at(end - 1))
+        })
+    }
+
+    fn index_of(&self, sum: i64) -> Option<usize> {
+        crate::index_of(sum, self.zero_index, self.sum_size)
+    }
+
+    fn sum_at(&self, index: usize) -> i64 {
+        index as i64 - self.zero_index as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_match_brute_force() {
+        let entries = [3, -7, 12, 5, -1, 0, 64];
+        let reachable = reachable_sums(&entries, &Options::default(), None).unwrap();
+
+        let mut expected: Vec<i64> = (0..1u32 << entries.len())
+            .map(|mask| (0..entries.len()).filter(|i| mask >> i & 1 != 0).map(|i| entries[i]).sum())
+            .collect();
+        expected.sort_unstable();
+        expected.dedup();
+
+        assert_eq!(reachable.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(reachable.count(), expected.len() as u64);
+        assert_eq!(reachable.ranges().flatten().collect::<Vec<_>>(), expected);
+        assert!(reachable.ranges().all(|range| !reachable.is_reachable(range.end() + 1)));
+
+        for sum in -100..=100 {
+            assert_eq!(reachable.is_reachable(sum), expected.contains(&sum), "{sum}");
+
+            let nearest = reachable.nearest_reachable(sum);
+            let distance = expected.iter().map(|reachable| reachable.abs_diff(sum)).min().unwrap();
+            assert_eq!(nearest.abs_diff(sum), distance, "{sum}");
+        }
+        assert_eq!(reachable.nearest_reachable(i64::MAX), 84);
+        assert_eq!(reachable.nearest_reachable(i64::MIN), -8);
+    }
+
+    #[test]
+    fn no_entries_only_reach_zero() {
+        let reachable = reachable_sums(&[], &Options::default(), None).unwrap();
+        assert_eq!(reachable.iter().collect::<Vec<_>>(), [0]);
+        assert_eq!(reachable.ranges().collect::<Vec<_>>(), [0..=0]);
+        assert_eq!(reachable.nearest_reachable(-5), 0);
+    }
+}
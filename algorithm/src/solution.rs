@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use crate::{Kernel, Strategy};
+
+/// A subset picked by a solve, along with how it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Solution {
+    /// Positions of the picked entries in the solved entry list, in increasing order.
+    pub indices: Vec<usize>,
+    /// The picked entries, in the same order as `indices`.
+    pub amounts: Vec<i64>,
+    /// What `amounts` add up to.
+    pub sum: i64,
+    pub target: i64,
+    /// Where the subset came from. `strategy`, `kernel`, `rows` and `sum_size` describe
+    /// the table the solve filled either way, except for an
+    /// [approximation](crate::Approximation), which fills none.
+    pub source: Source,
+    pub strategy: Strategy,
+    pub kernel: Kernel,
+    /// Time from the start of the solve until this solution was picked.
+    pub elapsed: Duration,
+    /// The table's size: one row per entry, and `sum_size` bits per row.
+    pub rows: usize,
+    pub sum_size: usize,
+    /// Whether [`verify`](Solution::verify) passed against the solved entries.
+    pub verified: bool,
+}
+
+/// How a [`Solution`]'s subset was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Source {
+    /// Backtracked from the DP table, which may have been only partly filled.
+    Table,
+    /// Picked by the heuristic search run after the table ran out of time.
+    Heuristic,
+    /// Picked by the [approximate solver](crate::approximate), without a table.
+    Approximation,
+}
+
+impl Solution {
+    /// Checks this solution against `entries`: that the indices are distinct and in
+    /// range, that they pick exactly `amounts`, and that `amounts` add up to `sum`.
+    ///
+    /// This doesn't require `sum` to equal `target`, as a best-effort solution may not.
+    pub fn verify(&self, entries: &[i64]) -> bool {
+        let increasing = self.indices.windows(2).all(|pair| pair[0] < pair[1]);
+        let picked = self.indices.len() == self.amounts.len()
+            && self.indices.iter()
+                .zip(&self.amounts)
+                .all(|(&i, &amount)| entries.get(i) == Some(&amount));
+        let adds_up = self.amounts.iter().map(|&amount| amount as i128).sum::<i128>() == self.sum as i128;
+
+        increasing && picked && adds_up
+    }
+
+    /// Whether this solution adds up to exactly the target.
+    pub fn is_exact(&self) -> bool {
+        self.sum == self.target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_catches_tampering() {
+        let entries = [4, -3, 9, 7];
+        let solution = crate::run_algorithm(13, entries.to_vec(), &crate::Options::default(), None).unwrap();
+        let crate::Outcome::Exact(Some(solution)) = solution else { panic!("13 is reachable") };
+        assert!(solution.verified && solution.is_exact());
+        assert_eq!(solution.amounts, solution.indices.iter().map(|&i| entries[i]).collect::<Vec<_>>());
+
+        let tampered = [
+            Solution { sum: solution.sum + 1, ..solution.clone() },
+            Solution { amounts: solution.amounts.iter().rev().copied().collect(), ..solution.clone() },
+            Solution { indices: solution.indices.iter().map(|i| i + 10).collect(), ..solution.clone() },
+        ];
+        for solution in tampered {
+            assert!(!solution.verify(&entries), "{solution:?}");
+        }
+    }
+}
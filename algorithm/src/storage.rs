@@ -0,0 +1,198 @@
+//! Where the rows a solve holds on to live: in memory, compressed in memory, or in a
+//! memory-mapped temporary file so tables larger than physical memory can still be
+//! solved, if slowly.
+
+use std::{borrow::Cow, fmt, path::Path, str::FromStr};
+
+use memmap2::MmapMut;
+
+use crate::{compressed::CompressedTable, create_dp_table, kernel, SolveError};
+
+/// Where to keep the DP rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Storage {
+    /// One heap allocation per row.
+    Memory,
+    /// One memory-mapped temporary file holding the rows back to back. The operating
+    /// system pages rows in and out as they are used, so the table may be larger than
+    /// physical memory. The file is deleted when the solve ends.
+    Mapped,
+    /// Each row compressed once filled, cut into chunks kept as a list of set bits, a list
+    /// of runs or plain words, whichever is smallest. Rows with long stretches of
+    /// unreachable or reachable sums shrink a lot, at the cost of compressing every row
+    /// and decompressing the rows read back.
+    Compressed,
+}
+
+impl Storage {
+    pub const ALL: [Storage; 3] = [Storage::Memory, Storage::Mapped, Storage::Compressed];
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Storage::Memory => write!(f, "memory"),
+            Storage::Mapped => write!(f, "mapped"),
+            Storage::Compressed => write!(f, "compressed"),
+        }
+    }
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Storage::ALL.into_iter()
+            .find(|storage| storage.to_string() == s)
+            .ok_or_else(|| format!("unknown storage `{s}` (expected one of: memory, mapped, compressed)"))
+    }
+}
+
+/// A fixed number of rows of `words` words each, all starting out cleared.
+pub(crate) enum Table {
+    Memory(Vec<Vec<u64>>),
+    /// Row `i` is words `i * words..(i + 1) * words` of the map.
+    Mapped { map: MmapMut, words: usize },
+    Compressed(CompressedTable),
+}
+
+impl Table {
+    /// Allocates `rows` rows. Mapped tables are backed by an unnamed file in `dir`, or
+    /// in the system temp directory if `None`.
+    pub(crate) fn new(storage: Storage, words: usize, rows: usize, dir: Option<&Path>) -> Result<Table, SolveError> {
+        match storage {
+            Storage::Memory => Ok(Table::Memory(create_dp_table(words, rows))),
+            Storage::Mapped => {
+                let error = |err: std::io::Error| SolveError::Storage(err.to_string());
+                let len = rows.checked_mul(words)
+                    .and_then(|words| words.checked_mul(8))
+                    .ok_or_else(|| SolveError::Storage(format!("{rows} rows of {words} words don't fit in a file")))?;
+
+                let file = match dir {
+                    Some(dir) => tempfile::tempfile_in(dir),
+                    None => tempfile::tempfile(),
+                }
+                .map_err(error)?;
+                file.set_len(len as u64).map_err(error)?;
+
+                // SAFETY: the file is unnamed, so nothing else can change it while mapped
+                let map = unsafe { MmapMut::map_mut(&file) }.map_err(error)?;
+                log::debug!("Mapped {rows} rows into a temporary file");
+
+                Ok(Table::Mapped { map, words })
+            }
+            Storage::Compressed => Ok(Table::Compressed(CompressedTable::new(words, rows))),
+        }
+    }
+
+    /// Row `i`, decompressed first if compressed.
+    pub(crate) fn row(&self, i: usize) -> Cow<'_, [u64]> {
+        match self {
+            Table::Memory(rows) => Cow::Borrowed(&rows[i]),
+            Table::Mapped { map, words } => Cow::Borrowed(&mapped_words(map)[i * words..(i + 1) * words]),
+            Table::Compressed(table) => table.row(i),
+        }
+    }
+
+    /// Whether sum index `index` is set in row `i`, without decompressing the row.
+    pub(crate) fn get(&self, i: usize, index: usize) -> bool {
+        match self {
+            Table::Compressed(table) => table.get(i, index),
+            _ => kernel::get(&self.row(i), index),
+        }
+    }
+
+    /// Overwrites row `i` with `row`.
+    pub(crate) fn set_row(&mut self, i: usize, row: &[u64]) {
+        match self {
+            Table::Compressed(table) => table.set_row(i, row),
+            _ => self.split_at_row(i).1.copy_from_slice(row),
+        }
+    }
+
+    /// Calls `f` with row `i - 1`, if there is one, and row `i` to fill. Row `i` must
+    /// not have been written to yet.
+    pub(crate) fn fill_row(&mut self, i: usize, f: impl FnOnce(Option<&[u64]>, &mut [u64])) {
+        match self {
+            Table::Compressed(table) => table.fill_row(i, f),
+            _ => {
+                let (previous, row) = self.split_at_row(i);
+                f(previous, row)
+            }
+        }
+    }
+
+    /// How much memory the rows take up, if they are compressed.
+    pub(crate) fn compressed_bytes(&self) -> Option<u64> {
+        match self {
+            Table::Compressed(table) => Some(table.heap_bytes() as u64),
+            _ => None,
+        }
+    }
+
+    /// Row `i - 1`, if there is one, alongside row `i` to write to.
+    fn split_at_row(&mut self, i: usize) -> (Option<&[u64]>, &mut [u64]) {
+        match self {
+            Table::Memory(rows) => {
+                let (done, rest) = rows.split_at_mut(i);
+                (done.last().map(Vec::as_slice), &mut rest[0])
+            }
+            Table::Mapped { map, words } => {
+                let words = *words;
+                let (done, rest) = mapped_words_mut(map).split_at_mut(i * words);
+                (i.checked_sub(1).map(|_| &done[done.len() - words..]), &mut rest[..words])
+            }
+            Table::Compressed(_) => unreachable!("compressed rows are filled through `fill_row`"),
+        }
+    }
+}
+
+fn mapped_words(map: &MmapMut) -> &[u64] {
+    // SAFETY: maps are page aligned, so aligned for `u64`, and a whole number of words long
+    unsafe { std::slice::from_raw_parts(map.as_ptr().cast(), map.len() / 8) }
+}
+
+fn mapped_words_mut(map: &mut MmapMut) -> &mut [u64] {
+    // SAFETY: as in `mapped_words`
+    unsafe { std::slice::from_raw_parts_mut(map.as_mut_ptr().cast(), map.len() / 8) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_storage_holds_the_same_rows() {
+        for storage in Storage::ALL {
+            let mut table = Table::new(storage, 3, 5, None).unwrap();
+            assert_eq!(*table.row(2), [0; 3]);
+
+            table.fill_row(0, |previous, row| {
+                assert!(previous.is_none());
+                row.copy_from_slice(&[1, 2, 3]);
+            });
+
+            for i in 1..4 {
+                table.fill_row(i, |previous, row| {
+                    for (word, previous) in row.iter_mut().zip(previous.unwrap()) {
+                        *word = previous * 2;
+                    }
+                });
+            }
+
+            assert_eq!(*table.row(3), [8, 16, 24], "{storage} storage");
+            assert_eq!(*table.row(1), [2, 4, 6], "{storage} storage");
+            assert!(table.get(2, 64 + 3) && !table.get(2, 64 + 2), "{storage} storage");
+
+            table.set_row(4, &[5, 0, 7]);
+            assert_eq!(*table.row(4), [5, 0, 7], "{storage} storage");
+            assert_eq!(table.compressed_bytes().is_some(), storage == Storage::Compressed);
+        }
+    }
+
+    #[test]
+    fn mapping_too_many_bytes_is_an_error() {
+        let err = Table::new(Storage::Mapped, usize::MAX / 8, 2, None).map(drop).unwrap_err();
+        assert!(matches!(err, SolveError::Storage(_)), "{err:?}");
+    }
+}
@@ -0,0 +1,40 @@
+//! Helpers shared by the integration tests.
+
+use adder_algorithm::Outcome;
+
+/// Whether some subset of `entries` adds up to `target`, by trying every one.
+pub fn reachable_by_brute_force(target: i64, entries: &[i64]) -> bool {
+    (0..1u32 << entries.len()).any(|mask| {
+        let sum: i128 = (0..entries.len())
+            .filter(|i| mask >> i & 1 != 0)
+            .map(|i| entries[i] as i128)
+            .sum();
+        sum == target as i128
+    })
+}
+
+/// Whether `subset` can be picked out of `entries`, using each entry at most once.
+pub fn is_sub_multiset(subset: &[i64], entries: &[i64]) -> bool {
+    let mut remaining = entries.to_vec();
+    subset.iter().all(|number| match remaining.iter().position(|entry| entry == number) {
+        Some(i) => {
+            remaining.swap_remove(i);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Panics unless `outcome` is the right exact answer for `target` and `entries`.
+pub fn assert_exact_answer(target: i64, entries: &[i64], outcome: &Outcome) {
+    match outcome {
+        Outcome::Exact(Some(solution)) => {
+            let subset = &solution.amounts;
+            assert_eq!(subset.iter().map(|&entry| entry as i128).sum::<i128>(), target as i128, "{subset:?} doesn't add up to {target}");
+            assert!(is_sub_multiset(subset, entries), "{subset:?} isn't a subset of {entries:?}");
+            assert!(solution.verified && solution.verify(entries) && solution.is_exact(), "{solution:?} doesn't check out against {entries:?}");
+        }
+        Outcome::Exact(None) => assert!(!reachable_by_brute_force(target, entries), "{target} is reachable from {entries:?}"),
+        Outcome::BestEffort(best) => panic!("expected an exact answer without a deadline, got {best:?}"),
+    }
+}
@@ -0,0 +1,62 @@
+//! Solves that run out of time: what comes back once the deadline has already passed
+//! when the table has only just started filling.
+
+use std::time::Instant;
+
+use adder_algorithm::{Options, Outcome, Source, Strategy};
+
+mod common;
+
+use common::{assert_exact_answer, is_sub_multiset};
+
+fn solve_by_now(target: i64, entries: &[i64], strategy: Strategy) -> Outcome {
+    let options = Options { strategy: Some(strategy), deadline: Some(Instant::now()), ..Options::default() };
+    adder_algorithm::run_algorithm(target, entries.to_vec(), &options, None)
+        .unwrap_or_else(|err| panic!("{strategy} failed: {err}"))
+}
+
+/// 2, 4, ..., 60: only even sums are reachable.
+fn evens() -> Vec<i64> {
+    (1..=30).map(|k| 2 * k).collect()
+}
+
+#[test]
+fn a_passed_deadline_gives_a_verified_best_effort() {
+    let entries = evens();
+
+    for strategy in Strategy::ALL {
+        match solve_by_now(61, &entries, strategy) {
+            Outcome::BestEffort(best) => {
+                assert!(best.rows_completed < entries.len(), "{strategy}: {best:?}");
+                assert!(best.closest.verified && best.closest.verify(&entries), "{strategy}: {best:?}");
+                assert!(is_sub_multiset(&best.closest.amounts, &entries), "{strategy}: {best:?}");
+                assert_eq!(best.closest.sum.abs_diff(61), 1, "{strategy}: {best:?}");
+            }
+            outcome => panic!("{strategy}: expected a best effort, got {outcome:?}"),
+        }
+    }
+}
+
+#[test]
+fn an_exact_hit_from_the_table_is_exact() {
+    // reachable from the first row alone
+    let entries = [5, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20];
+
+    for strategy in Strategy::ALL {
+        let outcome = solve_by_now(5, &entries, strategy);
+        assert_exact_answer(5, &entries, &outcome);
+        assert!(matches!(outcome, Outcome::Exact(Some(solution)) if solution.source == Source::Table));
+    }
+}
+
+#[test]
+fn an_exact_hit_from_the_heuristic_is_exact() {
+    // the first row only reaches 0 and 2, but the greedy pick takes 60 straight away
+    let entries = evens();
+
+    for strategy in Strategy::ALL {
+        let outcome = solve_by_now(60, &entries, strategy);
+        assert_exact_answer(60, &entries, &outcome);
+        assert!(matches!(outcome, Outcome::Exact(Some(solution)) if solution.source == Source::Heuristic));
+    }
+}
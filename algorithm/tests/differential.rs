@@ -0,0 +1,131 @@
+//! Compares every way of running the solver against an exhaustive search over all
+//! `2^n` subsets, for sets small enough to enumerate.
+
+use adder_algorithm::{Kernel, Options, Outcome, Storage, Strategy};
+use proptest::{prelude::*, strategy::Strategy as _};
+
+mod common;
+
+use common::assert_exact_answer;
+
+/// Every combination of strategy, kernel, storage and early exit this machine can run.
+fn all_options() -> Vec<Options> {
+    let mut all = vec![];
+    for strategy in Strategy::ALL {
+        for kernel in Kernel::ALL.into_iter().filter(|kernel| kernel.is_available()) {
+            for storage in Storage::ALL {
+                for early_exit in [false, true] {
+                    all.push(Options {
+                        strategy: Some(strategy),
+                        kernel: Some(kernel),
+                        storage: Some(storage),
+                        early_exit,
+                        ..Options::default()
+                    });
+                }
+            }
+        }
+    }
+    all
+}
+
+fn solve(target: i64, entries: &[i64], options: &Options) -> Outcome {
+    adder_algorithm::run_algorithm(target, entries.to_vec(), options, None)
+        .unwrap_or_else(|err| panic!("{options:?} failed: {err}"))
+}
+
+/// Entries drawn mostly from a handful of small values, so duplicates and zeros are
+/// common, with the odd larger one to spread the sums across several words.
+fn entries() -> impl prop::strategy::Strategy<Value = Vec<i64>> {
+    let entry = prop_oneof![
+        3 => -3i64..=3,
+        1 => -300i64..=300,
+    ];
+    prop::collection::vec(entry, 0..=14)
+}
+
+/// A set of entries and a target near the range of sums they can reach.
+fn problem() -> impl prop::strategy::Strategy<Value = (Vec<i64>, i64)> {
+    entries().prop_flat_map(|entries| {
+        let lowest: i64 = entries.iter().filter(|&&entry| entry < 0).sum();
+        let highest: i64 = entries.iter().filter(|&&entry| entry > 0).sum();
+        (Just(entries), lowest - 3..=highest + 3)
+    })
+}
+
+/// Only negative entries, and a target near the range of sums they can reach.
+fn all_negative_problem() -> impl prop::strategy::Strategy<Value = (Vec<i64>, i64)> {
+    prop::collection::vec(-50i64..=-1, 1..=12).prop_flat_map(|entries| {
+        let lowest: i64 = entries.iter().sum();
+        (Just(entries), lowest - 2..=2)
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn every_configuration_agrees_with_brute_force((entries, target) in problem()) {
+        for options in all_options() {
+            assert_exact_answer(target, &entries, &solve(target, &entries, &options));
+        }
+    }
+
+    #[test]
+    fn all_negative_entries((entries, target) in all_negative_problem()) {
+        for options in all_options() {
+            assert_exact_answer(target, &entries, &solve(target, &entries, &options));
+        }
+    }
+}
+
+/// Edge cases worth pinning down exactly, as `(target, entries)`.
+const FIXTURES: &[(i64, &[i64])] = &[
+    // a single entry
+    (7, &[7]),
+    (-7, &[-7]),
+    (0, &[7]),
+    (3, &[7]),
+    // target 0
+    (0, &[]),
+    (0, &[0]),
+    (0, &[5, -5]),
+    (0, &[1, 2, 3]),
+    // all negative
+    (-6, &[-1, -2, -3]),
+    (-7, &[-1, -2, -3]),
+    (1, &[-1, -2, -3]),
+    (i64::MIN, &[-1, -2, -3]),
+    // duplicates
+    (6, &[2, 2, 2]),
+    (8, &[2, 2, 2]),
+    (0, &[-4, -4, 4, 4]),
+    // zeros
+    (0, &[0, 0, 0]),
+    (1, &[0, 0, 0]),
+    (5, &[0, 5, 0]),
+    // sums spanning several words
+    (129, &[64, 65, -1, 1]),
+    (-200, &[-100, -64, -36, 300]),
+    // far outside every sum
+    (i64::MAX, &[1, 2, 3]),
+];
+
+#[test]
+fn fixtures() {
+    for &(target, entries) in FIXTURES {
+        for options in all_options() {
+            let outcome = solve(target, entries, &options);
+            assert_exact_answer(target, entries, &outcome);
+        }
+    }
+}
+
+#[test]
+fn fixtures_never_use_zero_entries() {
+    for &(target, entries) in FIXTURES {
+        if let Outcome::Exact(Some(solution)) = solve(target, entries, &Options::default()) {
+            assert!(!solution.amounts.contains(&0), "{solution:?} for {target} from {entries:?}");
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use adder_algorithm::{Options, Outcome, SolveError};
+use proptest::prelude::*;
+
+mod common;
+
+use common::assert_exact_answer;
+
+fn solve(target: i64, entries: &[i64]) -> Result<Outcome, SolveError> {
+    adder_algorithm::run_algorithm(target, entries.to_vec(), &Options::default(), None)
+}
+
+/// The amounts picked by an exact solve, or `None` if there is no correct subset.
+fn amounts(target: i64, entries: &[i64]) -> Option<Vec<i64>> {
+    match solve(target, entries).unwrap() {
+        Outcome::Exact(solution) => solution.map(|solution| solution.amounts),
+        Outcome::BestEffort(best) => panic!("expected an exact answer without a deadline, got {best:?}"),
+    }
+}
+
+proptest! {
+    #[test]
+    fn agrees_with_brute_force(entries in prop::collection::vec(-40i64..=40, 0..12), target in -200i64..=200) {
+        let outcome = solve(target, &entries).unwrap();
+        assert_exact_answer(target, &entries, &outcome);
+
+        if let Outcome::Exact(Some(solution)) = outcome {
+            prop_assert!(!solution.amounts.contains(&0));
+        }
+    }
+
+    #[test]
+    fn targets_outside_every_sum_are_unreachable(entries in prop::collection::vec(-40i64..=40, 0..12), target in any::<i64>()) {
+        let lowest: i64 = entries.iter().filter(|&&entry| entry < 0).sum();
+        let highest: i64 = entries.iter().filter(|&&entry| entry > 0).sum();
+        prop_assume!(target < lowest || target > highest);
+
+        prop_assert_eq!(amounts(target, &entries), None);
+    }
+}
+
+#[test]
+fn extreme_entries_are_rejected_not_wrapped() {
+    for entries in [vec![i64::MIN], vec![i64::MAX], vec![i64::MAX / 2, i64::MAX / 2, 3], vec![-1, i64::MIN + 1, -5]] {
+        assert_eq!(solve(0, &entries), Err(SolveError::SumsTooWide), "{entries:?}");
+    }
+}
+
+#[test]
+fn zero_entries_are_never_needed() {
+    assert_eq!(amounts(0, &[0]), Some(vec![]));
+    assert_eq!(amounts(0, &[0, 0, 0]), Some(vec![]));
+    assert_eq!(amounts(5, &[0, 5, 0]), Some(vec![5]));
+    assert_eq!(amounts(1, &[0, 0]), None);
+}
+
+#[test]
+fn no_entries_only_reach_zero() {
+    assert_eq!(amounts(0, &[]), Some(vec![]));
+    assert_eq!(amounts(i64::MIN, &[]), None);
+}
@@ -0,0 +1,69 @@
+//! Solves interrupted partway through and picked up again from their checkpoint, compared
+//! with the same solve run through uninterrupted.
+
+use std::{panic::{self, AssertUnwindSafe}, sync::Mutex};
+
+use adder_algorithm::{CheckpointOptions, Observer, Options, Outcome, Strategy};
+
+/// Records the rows started, and panics on starting row `interrupt_at`, as if the process
+/// had been killed there.
+struct Interrupt {
+    interrupt_at: Option<usize>,
+    started: Mutex<Vec<usize>>,
+}
+
+impl Interrupt {
+    fn at(interrupt_at: Option<usize>) -> Interrupt {
+        Interrupt { interrupt_at, started: Mutex::new(vec![]) }
+    }
+}
+
+impl Observer for Interrupt {
+    fn row_started(&self, row: usize, _total: usize) {
+        if self.interrupt_at == Some(row) {
+            panic!("interrupted at row {row}");
+        }
+        self.started.lock().unwrap().push(row);
+    }
+}
+
+/// The picked indices and their sum, leaving out timings.
+fn answer(outcome: Outcome) -> Option<(Vec<usize>, i64)> {
+    match outcome {
+        Outcome::Exact(solution) => solution.map(|solution| (solution.indices, solution.sum)),
+        Outcome::BestEffort(best) => panic!("no deadline was set, got {best:?}"),
+    }
+}
+
+#[test]
+fn resuming_gives_the_same_answer_as_an_uninterrupted_run() {
+    let entries: Vec<i64> = (0..20).map(|i| (i * 37 % 23) - 9).collect();
+    let target = 31;
+
+    // (strategy, row to interrupt at, first row filled after resuming): the low-memory
+    // strategy keeps every 5th row of 20, so it goes back to the last one it kept
+    for (strategy, interrupt_at, resumes_at) in [(Strategy::Full, 13, 13), (Strategy::LowMemory, 13, 10)] {
+        let dir = tempfile::tempdir().unwrap();
+        let options = Options {
+            strategy: Some(strategy),
+            checkpoint: Some(CheckpointOptions { path: dir.path().join("checkpoint"), every: 1 }),
+            ..Options::default()
+        };
+
+        let interrupt = Interrupt::at(Some(interrupt_at));
+        let interrupted = panic::catch_unwind(AssertUnwindSafe(|| {
+            adder_algorithm::run_algorithm(target, entries.clone(), &options, Some(&interrupt))
+        }));
+        assert!(interrupted.is_err(), "{strategy}");
+
+        let resumed_rows = Interrupt::at(None);
+        let resumed = adder_algorithm::resume_algorithm(&options, Some(&resumed_rows)).unwrap();
+        assert_eq!(resumed_rows.started.lock().unwrap()[0], resumes_at, "{strategy}");
+
+        let uninterrupted = Options { strategy: Some(strategy), ..Options::default() };
+        let expected = adder_algorithm::run_algorithm(target, entries.clone(), &uninterrupted, None).unwrap();
+        let answer_resumed = answer(resumed);
+        assert!(answer_resumed.is_some(), "{target} is reachable from {entries:?}");
+        assert_eq!(answer_resumed, answer(expected), "{strategy}");
+    }
+}
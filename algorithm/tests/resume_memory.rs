@@ -0,0 +1,73 @@
+//! How much a resumed solve allocates: the saved rows go straight into the table, so a
+//! budget that fits one table is enough to resume with.
+//!
+//! This is its own test binary so that nothing else allocates while the peak is measured.
+
+use std::{alloc::{GlobalAlloc, Layout, System}, panic::{self, AssertUnwindSafe}, sync::atomic::{AtomicU64, Ordering}};
+
+use adder_algorithm::{CheckpointOptions, Observer, Options, Outcome, Strategy};
+
+/// The system allocator, keeping track of the most bytes allocated at once.
+struct Peak;
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for Peak {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size() as u64, Ordering::SeqCst) + layout.size() as u64;
+        PEAK.fetch_max(allocated, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size() as u64, Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Peak = Peak;
+
+/// Panics on starting row `at`, as if the process had been killed there.
+struct Interrupt {
+    at: usize,
+}
+
+impl Observer for Interrupt {
+    fn row_started(&self, row: usize, _total: usize) {
+        if row == self.at {
+            panic!("interrupted at row {row}");
+        }
+    }
+}
+
+#[test]
+fn resuming_fits_a_budget_of_one_table() {
+    // sums 0..=4_000_000, about 500 KB a row
+    let entries: Vec<i64> = (0..40).map(|i| 100_000 - i).collect();
+    let target = entries[..20].iter().sum();
+    let table = adder_algorithm::estimate_memory(&entries, Strategy::Full);
+
+    let dir = tempfile::tempdir().unwrap();
+    let options = Options {
+        strategy: Some(Strategy::Full),
+        checkpoint: Some(CheckpointOptions { path: dir.path().join("checkpoint"), every: 1 }),
+        ..Options::default()
+    };
+
+    let interrupted = panic::catch_unwind(AssertUnwindSafe(|| {
+        adder_algorithm::run_algorithm(target, entries.clone(), &options, Some(&Interrupt { at: 35 }))
+    }));
+    assert!(interrupted.is_err());
+
+    let budget = table + table / 2;
+    let options = Options { memory_budget: Some(budget), ..options };
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let resumed = adder_algorithm::resume_algorithm(&options, None).unwrap();
+    let peak = PEAK.load(Ordering::SeqCst) - before;
+
+    assert!(matches!(resumed, Outcome::Exact(Some(solution)) if solution.sum == target));
+    assert!(peak <= budget, "resuming allocated {peak} bytes at once, the table alone is {table}");
+}
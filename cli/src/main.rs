@@ -1,21 +1,301 @@
-use std::io::{Write, BufRead};
+use std::{io::{Write, BufRead}, path::PathBuf, time::{Duration, Instant}};
+
+use adder_algorithm::{ApproxOptions, Bytes, CheckpointHeader, CheckpointOptions, Kernel, Options, Outcome, SolveError, Storage, Strategy};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(about = "Finds a subset of entries that adds up to a target")]
+struct Args {
+    /// Refuse to allocate more than this much for the DP table, e.g. `512M` or `8G`.
+    /// Falls back to the low-memory strategy if the full table won't fit. Also bounds
+    /// the approximate solver's lists.
+    #[arg(long)]
+    memory_budget: Option<Bytes>,
+
+    /// Force a strategy (`full` or `low-memory`) instead of picking one automatically.
+    #[arg(long)]
+    strategy: Option<Strategy>,
+
+    /// Number of threads used to fill the table. Defaults to one per core.
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Row kernel to use (`scalar`, `simd` or `avx2`). Defaults to the fastest this CPU supports.
+    #[arg(long, global = true)]
+    kernel: Option<Kernel>,
+
+    /// Where to keep the DP rows: `memory`, `mapped` to a temporary file so tables larger
+    /// than RAM can be solved (slowly), or `compressed` in memory, which shrinks rows with
+    /// long runs of equal bits. The memory budget isn't checked when mapped, and is checked
+    /// as rows are filled when compressed.
+    #[arg(long)]
+    storage: Option<Storage>,
+
+    /// Directory for the `mapped` storage file. Defaults to the system temp directory.
+    #[arg(long)]
+    storage_dir: Option<PathBuf>,
+
+    /// Stop as soon as the target is reachable instead of filling the whole table.
+    #[arg(long)]
+    early_exit: bool,
+
+    /// Give up on an exact answer after this long (e.g. `10s`, `500ms`, `2m`) and print
+    /// the closest subset found instead. Limits the approximate solver too.
+    #[arg(long, value_parser = parse_duration)]
+    time_limit: Option<Duration>,
+
+    /// Skip the exact search and use the approximate solver. It is also used automatically
+    /// when the exact table won't fit in the memory budget.
+    #[arg(long)]
+    approximate: bool,
+
+    /// How far from optimal the approximate solver may be, as a fraction of the sum of
+    /// the absolute values of the entries.
+    #[arg(long, default_value_t = ApproxOptions::default().epsilon)]
+    epsilon: f64,
+
+    /// Save progress to this file as the table is filled, so an interrupted run can be
+    /// picked up again with `--resume`.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Save a checkpoint every this many rows.
+    #[arg(long, default_value_t = 100)]
+    checkpoint_every: usize,
+
+    /// Carry on from the `--checkpoint` file instead of reading a new problem.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lists every total some subset of the entries adds up to, instead of looking for a
+    /// target. Reads only the entries.
+    Reachable {
+        /// Print runs of consecutive totals as `low..=high` instead of every total.
+        #[arg(long)]
+        ranges: bool,
+
+        /// Write the totals to this file, one per line, instead of printing them.
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Only check these totals, printing the nearest reachable total for any that
+        /// aren't reachable.
+        #[arg(long, num_args = 1.., allow_negative_numbers = true)]
+        check: Vec<i64>,
+    },
+}
 
 fn main() {
-    let Input { target, entries } = gather_input();
+    let args = Args::parse();
+    log::set_logger(&PrintLogger).expect("no other logger is installed");
+    log::set_max_level(log::LevelFilter::Trace);
 
-    match adder_algorithm::run_algorithm(target, entries, None) {
-        Some(subset) => {
+    if let Some(Command::Reachable { ranges, output, check }) = args.command {
+        let options = Options { threads: args.threads, kernel: args.kernel, ..Options::default() };
+        reachable(&gather_entries(), &options, ranges, output, &check);
+        return;
+    }
+
+    let Input { target, entries } = match (&args.checkpoint, args.resume) {
+        (Some(path), true) => match CheckpointHeader::read(path) {
+            Ok(header) => Input { target: header.target, entries: header.entries },
+            Err(err) => {
+                eprintln!("Error: {err}");
+                std::process::exit(1);
+            }
+        },
+        _ => gather_input(),
+    };
+    let n_entries = entries.len();
+
+    let memory_budget = args.memory_budget.map(|Bytes(budget)| budget);
+    let deadline = args.time_limit.map(|limit| Instant::now() + limit);
+
+    let approx_options = ApproxOptions {
+        epsilon: args.epsilon,
+        memory_budget,
+        deadline,
+        ..ApproxOptions::default()
+    };
+
+    if args.approximate {
+        approximate(target, &entries, &approx_options);
+        return;
+    }
+
+    let options = Options {
+        strategy: args.strategy,
+        memory_budget,
+        threads: args.threads,
+        kernel: args.kernel,
+        early_exit: args.early_exit,
+        deadline,
+        storage: args.storage,
+        storage_dir: args.storage_dir,
+        checkpoint: args.checkpoint.map(|path| CheckpointOptions { path, every: args.checkpoint_every }),
+        ..Options::default()
+    };
+
+    println!("Estimated memory:");
+    for strategy in Strategy::ALL {
+        println!("  {strategy}: {}", Bytes(adder_algorithm::estimate_memory(&entries, strategy)));
+    }
+
+    let result = if args.resume {
+        adder_algorithm::resume_algorithm(&options, None)
+    } else {
+        adder_algorithm::run_algorithm(target, entries.clone(), &options, None)
+    };
+
+    match result {
+        Ok(Outcome::Exact(Some(solution))) => {
             println!("A correct subset:");
-            for number in subset {
+            for number in solution.amounts {
                 println!("{number}");
             }
         }
-        None => {
+        Ok(Outcome::Exact(None)) => {
             println!("There is no correct subset")
-        }     
+        }
+        Ok(Outcome::BestEffort(best)) => {
+            println!("Ran out of time after checking {} of {n_entries} entries.", best.rows_completed);
+            println!("A correct subset may still exist. The closest subset found adds up to {} ({} off):", best.closest.sum, best.closest.sum.abs_diff(target));
+            for number in best.closest.amounts {
+                println!("{number}");
+            }
+        }
+        Err(err @ (SolveError::MemoryBudgetExceeded { .. } | SolveError::SumsTooWide)) => {
+            println!("The exact search can't run: {err}. Falling back to the approximate solver.");
+            approximate(target, &entries, &approx_options);
+        }
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints everything the solver logs to stdout, as its progress report.
+struct PrintLogger;
+
+impl log::Log for PrintLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        println!("{}", record.args());
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
     }
 }
 
+/// Runs the approximate solver and prints its answer, unless its lists won't fit in the
+/// memory budget even at their coarsest.
+fn approximate(target: i64, entries: &[i64], options: &ApproxOptions) {
+    let required = adder_algorithm::estimate_approx_memory(entries, options);
+    println!("Estimated memory for the approximate solver: {}", Bytes(required));
+    if let Some(budget) = options.memory_budget.filter(|&budget| required > budget) {
+        eprintln!("Error: the approximate solver needs {} but the memory budget is {}", Bytes(required), Bytes(budget));
+        std::process::exit(1);
+    }
+
+    let approximation = adder_algorithm::approximate(target, entries, options);
+    let solution = &approximation.solution;
+    if approximation.rows_completed < entries.len() {
+        println!("Ran out of time after checking {} of {} entries.", approximation.rows_completed, entries.len());
+    }
+
+    if approximation.distance == 0 {
+        println!("A correct subset:");
+    } else if approximation.target_unreachable() {
+        println!("There is no correct subset. The closest subset found adds up to {} ({} off):", solution.sum, approximation.distance);
+    } else {
+        println!("No correct subset was found, but one may still exist.");
+        println!("The closest subset found adds up to {} ({} off from {target}, at most {} further than the best possible):",
+            solution.sum, approximation.distance, approximation.error_bound);
+    }
+
+    for number in &solution.amounts {
+        println!("{number}");
+    }
+}
+
+fn reachable(entries: &[i64], options: &Options, ranges: bool, output: Option<PathBuf>, check: &[i64]) {
+    let reachable = match adder_algorithm::reachable_sums(entries, options, None) {
+        Ok(reachable) => reachable,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    if !check.is_empty() {
+        for &total in check {
+            if reachable.is_reachable(total) {
+                println!("{total}: reachable");
+            } else {
+                let nearest = reachable.nearest_reachable(total);
+                println!("{total}: not reachable, the nearest reachable total is {nearest} ({} off)", nearest.abs_diff(total));
+            }
+        }
+        return;
+    }
+
+    println!("{} reachable totals", reachable.count());
+
+    let mut out: Box<dyn Write> = match &output {
+        Some(path) => match std::fs::File::create(path) {
+            Ok(file) => Box::new(std::io::BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("Error: could not create {}: {err}", path.display());
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    let written = if ranges {
+        reachable.ranges().try_for_each(|range| writeln!(out, "{}..={}", range.start(), range.end()))
+    } else {
+        reachable.iter().try_for_each(|total| writeln!(out, "{total}"))
+    };
+
+    if let Err(err) = written.and_then(|()| out.flush()) {
+        eprintln!("Error: could not write the totals: {err}");
+        std::process::exit(1);
+    }
+    if let Some(path) = output {
+        println!("Wrote them to {}", path.display());
+    }
+}
+
+/// Parses a duration like `10s`, `500ms` or `2m`. A bare number is in seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = s.find(|ch: char| ch.is_ascii_alphabetic())
+        .map_or((s, "s"), |i| s.split_at(i));
+
+    let number = number.trim().parse::<f64>().map_err(|_| format!("invalid duration `{s}`"))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("unknown unit in `{s}` (expected ms, s, m or h)")),
+    };
+
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid duration `{s}`"))
+}
+
 struct Input {
     target: i64,
     entries: Vec<i64>,
@@ -24,11 +304,18 @@ struct Input {
 fn gather_input() -> Input {
     print!("Please enter the target in cents: ");
     std::io::stdout().flush().unwrap();
-    
+
     let mut target = String::new();
     std::io::stdin().lock().read_line(&mut target).unwrap();
     let target = target.trim_end().parse().unwrap();
 
+    Input {
+        target,
+        entries: gather_entries(),
+    }
+}
+
+fn gather_entries() -> Vec<i64> {
     print!("Please enter the number of entries: ");
     std::io::stdout().flush().unwrap();
 
@@ -45,8 +332,5 @@ fn gather_input() -> Input {
         entries.push(entry.trim_end().parse().unwrap());
     }
 
-    Input {
-        target,
-        entries,
-    }
+    entries
 }